@@ -0,0 +1,373 @@
+use apu::channels::{SquareChannel, WaveChannel, NoiseChannel};
+
+//Bits that always read back as 1 for 0xFF10-0xFF2F
+pub static SOUND_READ_MASKS: [u8; 0x20] = [0x80, 0x3F, 0x00, 0xFF, 0xBF,
+                                           0xFF, 0x3F, 0x00, 0xFF, 0xBF,
+                                           0x7F, 0xFF, 0x9F, 0xFF, 0xBF,
+                                           0xFF, 0xFF, 0x00, 0x00, 0xBF,
+                                           0x00, 0x00, 0x70,
+                                           0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+pub struct APU
+{
+    pub power: bool,
+    pub channel_1: SquareChannel,
+    pub channel_2: SquareChannel,
+    pub channel_3: WaveChannel,
+    pub channel_4: NoiseChannel,
+    pub left_volume: u8,
+    pub right_volume: u8,
+    pub panning: u8,
+    pub frame_sequencer_step: u8,
+    pub previous_div_bit: bool,
+    pub sample_rate: f64,
+    pub sample_cycles: f64,
+    pub samples: Vec<f32>,
+}
+
+impl APU
+{
+    pub fn new() -> APU
+    {
+        return APU
+        {
+            power: false,
+            channel_1: SquareChannel::new(),
+            channel_2: SquareChannel::new(),
+            channel_3: WaveChannel::new(),
+            channel_4: NoiseChannel::new(),
+            left_volume: 0,
+            right_volume: 0,
+            panning: 0,
+            frame_sequencer_step: 0,
+            previous_div_bit: false,
+            sample_rate: 0.0,
+            sample_cycles: 0.0,
+            samples: Vec::new(),
+        }
+    }
+
+    //A sample rate of 0 turns sample output off
+    pub fn set_sample_rate(&mut self, sample_rate: f64)
+    {
+        self.sample_rate = sample_rate;
+    }
+
+    //Picks up register values written straight to memory, without triggering any channels
+    pub fn load_registers(&mut self, mem_map: &Vec<u8>)
+    {
+        self.write_register(0xFF26, mem_map[0xFF26]);
+        for location in 0xFF10..0xFF26
+        {
+            let mut value = mem_map[location];
+            if location == 0xFF14 || location == 0xFF19 || location == 0xFF1E || location == 0xFF23
+            {
+                value &= 0x7F;
+            }
+            self.write_register(location, value);
+        }
+        for location in 0xFF30..0xFF40
+        {
+            self.write_register(location, mem_map[location]);
+        }
+    }
+
+    pub fn write_register(&mut self, location: usize, value: u8)
+    {
+        if location >= 0xFF30 && location < 0xFF40
+        {
+            self.channel_3.wave_ram[location - 0xFF30] = value;
+            return;
+        }
+        if location == 0xFF26
+        {
+            let power = (value & 0x80) == 0x80;
+            if self.power && !power
+            {
+                self.power_off();
+            }
+            else if !self.power && power
+            {
+                self.frame_sequencer_step = 0;
+            }
+            self.power = power;
+            return;
+        }
+        if !self.power
+        {
+            return;
+        }
+
+        match location
+        {
+            0xFF10 => self.channel_1.sweep.write(value),
+            0xFF11 => self.channel_1.write_length_duty(value),
+            0xFF12 => self.channel_1.write_envelope(value),
+            0xFF13 => self.channel_1.write_frequency_low(value),
+            0xFF14 => self.channel_1.write_frequency_high(value, true),
+            0xFF16 => self.channel_2.write_length_duty(value),
+            0xFF17 => self.channel_2.write_envelope(value),
+            0xFF18 => self.channel_2.write_frequency_low(value),
+            0xFF19 => self.channel_2.write_frequency_high(value, false),
+            0xFF1A => self.channel_3.write_dac(value),
+            0xFF1B => self.channel_3.length.load(value),
+            0xFF1C => self.channel_3.write_volume(value),
+            0xFF1D => self.channel_3.write_frequency_low(value),
+            0xFF1E => self.channel_3.write_frequency_high(value),
+            0xFF20 => self.channel_4.write_length(value),
+            0xFF21 => self.channel_4.write_envelope(value),
+            0xFF22 => self.channel_4.write_polynomial(value),
+            0xFF23 => self.channel_4.write_control(value),
+            0xFF24 =>
+            {
+                self.left_volume = (value & 0x70) >> 4;
+                self.right_volume = value & 0x07;
+            },
+            0xFF25 => self.panning = value,
+            _ => (),
+        }
+    }
+
+    fn power_off(&mut self)
+    {
+        let wave_ram = self.channel_3.wave_ram.clone();
+        self.channel_1 = SquareChannel::new();
+        self.channel_2 = SquareChannel::new();
+        self.channel_3 = WaveChannel::new();
+        self.channel_3.wave_ram = wave_ram;
+        self.channel_4 = NoiseChannel::new();
+        self.left_volume = 0;
+        self.right_volume = 0;
+        self.panning = 0;
+    }
+
+    //NR52 low nibble shows which channels are still playing
+    pub fn status(&self) -> u8
+    {
+        let mut status = 0;
+        if self.power
+        {
+            status |= 0x80;
+        }
+        let channels = [self.channel_1.enabled, self.channel_2.enabled, self.channel_3.enabled, self.channel_4.enabled];
+        for i in 0..channels.len()
+        {
+            if channels[i]
+            {
+                status |= 1 << i;
+            }
+        }
+        return status;
+    }
+
+    //Frame sequencer runs at 512Hz off the falling edge of DIV bit 4
+    pub fn tick(&mut self, cycles: u8, div_bit: bool)
+    {
+        if self.previous_div_bit && !div_bit && self.power
+        {
+            self.step_frame_sequencer();
+        }
+        self.previous_div_bit = div_bit;
+
+        let mut remaining = cycles;
+        while remaining > 0
+        {
+            if self.power
+            {
+                self.channel_1.tick(4);
+                self.channel_2.tick(4);
+                self.channel_3.tick(4);
+                self.channel_4.tick(4);
+            }
+            if self.sample_rate > 0.0
+            {
+                self.sample_cycles += 4.0;
+                let cycles_per_sample = 4194304.0 / self.sample_rate;
+                while self.sample_cycles >= cycles_per_sample
+                {
+                    self.sample_cycles -= cycles_per_sample;
+                    self.push_sample();
+                }
+            }
+            remaining = remaining.saturating_sub(4);
+        }
+    }
+
+    fn step_frame_sequencer(&mut self)
+    {
+        if self.frame_sequencer_step % 2 == 0
+        {
+            self.channel_1.clock_length();
+            self.channel_2.clock_length();
+            self.channel_3.clock_length();
+            self.channel_4.clock_length();
+        }
+        if self.frame_sequencer_step == 2 || self.frame_sequencer_step == 6
+        {
+            self.channel_1.clock_sweep();
+        }
+        if self.frame_sequencer_step == 7
+        {
+            if self.channel_1.enabled
+            {
+                self.channel_1.envelope.clock();
+            }
+            if self.channel_2.enabled
+            {
+                self.channel_2.envelope.clock();
+            }
+            if self.channel_4.enabled
+            {
+                self.channel_4.envelope.clock();
+            }
+        }
+        self.frame_sequencer_step = (self.frame_sequencer_step + 1) % 8;
+    }
+
+    pub fn mix(&self) -> (f32, f32)
+    {
+        let outputs = [dac_output(self.channel_1.output(), self.channel_1.envelope.dac_enabled()),
+                       dac_output(self.channel_2.output(), self.channel_2.envelope.dac_enabled()),
+                       dac_output(self.channel_3.output(), self.channel_3.dac_enabled),
+                       dac_output(self.channel_4.output(), self.channel_4.envelope.dac_enabled())];
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for i in 0..outputs.len()
+        {
+            if (self.panning >> (i + 4)) & 0x01 == 0x01
+            {
+                left += outputs[i];
+            }
+            if (self.panning >> i) & 0x01 == 0x01
+            {
+                right += outputs[i];
+            }
+        }
+        left *= (self.left_volume + 1) as f32 / 8.0;
+        right *= (self.right_volume + 1) as f32 / 8.0;
+        return (left / 4.0, right / 4.0);
+    }
+
+    fn push_sample(&mut self)
+    {
+        let (left, right) = self.mix();
+        self.samples.push(left);
+        self.samples.push(right);
+    }
+
+    pub fn take_samples(&mut self) -> Vec<f32>
+    {
+        let mut samples = Vec::new();
+        ::std::mem::swap(&mut samples, &mut self.samples);
+        return samples;
+    }
+}
+
+//Maps a channel's 0-15 output to -1.0..1.0, DACs that are off output nothing
+fn dac_output(value: u8, dac_enabled: bool) -> f32
+{
+    if !dac_enabled
+    {
+        return 0.0;
+    }
+    return (value as f32 / 7.5) - 1.0;
+}
+
+#[cfg(test)]
+mod apu_tests
+{
+    use apu::apu::*;
+    use system::*;
+
+    #[test]
+    fn load_registers_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        system_data.mmu.mem_map[0xFF26] = 0xF1;
+        system_data.mmu.mem_map[0xFF24] = 0x77;
+        system_data.mmu.mem_map[0xFF25] = 0xF3;
+        system_data.mmu.mem_map[0xFF12] = 0xF3;
+        system_data.mmu.mem_map[0xFF14] = 0xC0;
+        system_data.apu.load_registers(&system_data.mmu.mem_map);
+        assert!(system_data.apu.power);
+        assert_eq!(system_data.apu.left_volume, 7);
+        assert_eq!(system_data.apu.right_volume, 7);
+        assert_eq!(system_data.apu.panning, 0xF3);
+        assert_eq!(system_data.apu.channel_1.envelope.initial_volume, 15);
+        assert!(system_data.apu.channel_1.length.enabled);
+        assert!(!system_data.apu.channel_1.enabled);
+    }
+
+    #[test]
+    fn power_off_test()
+    {
+        let mut apu = APU::new();
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF30, 0x12);
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF14, 0x80);
+        apu.write_register(0xFF24, 0x77);
+        assert_eq!(apu.status(), 0x81);
+        apu.write_register(0xFF26, 0x00);
+        assert_eq!(apu.status(), 0x00);
+        assert_eq!(apu.left_volume, 0);
+        assert_eq!(apu.channel_3.wave_ram[0], 0x12);
+        apu.write_register(0xFF24, 0x77);
+        assert_eq!(apu.left_volume, 0);
+    }
+
+    #[test]
+    fn frame_sequencer_length_test()
+    {
+        let mut apu = APU::new();
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF17, 0xF0);
+        apu.write_register(0xFF16, 0x3E);
+        apu.write_register(0xFF19, 0xC0);
+        assert_eq!(apu.status(), 0x82);
+        apu.tick(4, true);
+        apu.tick(4, false);
+        assert_eq!(apu.status(), 0x82);
+        apu.tick(4, true);
+        apu.tick(4, false);
+        assert_eq!(apu.status(), 0x82);
+        apu.tick(4, true);
+        apu.tick(4, false);
+        assert_eq!(apu.status(), 0x80);
+    }
+
+    #[test]
+    fn sample_output_test()
+    {
+        let mut apu = APU::new();
+        for _ in 0..1000
+        {
+            apu.tick(24, false);
+        }
+        assert!(apu.samples.is_empty());
+
+        apu.set_sample_rate(48000.0);
+        for _ in 0..(4194304 / 16)
+        {
+            apu.tick(16, false);
+        }
+        let samples = apu.take_samples();
+        assert_eq!(samples.len(), 96000);
+        assert!(apu.samples.is_empty());
+    }
+
+    #[test]
+    fn mix_panning_test()
+    {
+        let mut apu = APU::new();
+        apu.write_register(0xFF26, 0x80);
+        apu.write_register(0xFF24, 0x70);
+        apu.write_register(0xFF25, 0x10);
+        apu.write_register(0xFF12, 0xF0);
+        apu.write_register(0xFF11, 0x80);
+        apu.write_register(0xFF14, 0x80);
+        let (left, right) = apu.mix();
+        assert_eq!(left, 0.25);
+        assert_eq!(right, 0.0);
+    }
+}
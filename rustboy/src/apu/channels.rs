@@ -0,0 +1,697 @@
+static DUTY_PATTERNS: [[u8; 8]; 4] = [[0, 0, 0, 0, 0, 0, 0, 1],
+                                      [1, 0, 0, 0, 0, 0, 0, 1],
+                                      [1, 0, 0, 0, 0, 1, 1, 1],
+                                      [0, 1, 1, 1, 1, 1, 1, 0]];
+
+static NOISE_DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+pub struct LengthCounter
+{
+    pub enabled: bool,
+    pub counter: u16,
+    pub max: u16,
+}
+
+impl LengthCounter
+{
+    pub fn new(max: u16) -> LengthCounter
+    {
+        return LengthCounter
+        {
+            enabled: false,
+            counter: 0,
+            max: max,
+        }
+    }
+
+    pub fn load(&mut self, value: u8)
+    {
+        self.counter = self.max - (value as u16 & (self.max - 1));
+    }
+
+    pub fn trigger(&mut self)
+    {
+        if self.counter == 0
+        {
+            self.counter = self.max;
+        }
+    }
+
+    //Returns true when the counter runs out and the channel should turn off
+    pub fn clock(&mut self) -> bool
+    {
+        if self.enabled && self.counter > 0
+        {
+            self.counter -= 1;
+            return self.counter == 0;
+        }
+        return false;
+    }
+}
+
+pub struct VolumeEnvelope
+{
+    pub initial_volume: u8,
+    pub increase: bool,
+    pub period: u8,
+    pub timer: u8,
+    pub volume: u8,
+}
+
+impl VolumeEnvelope
+{
+    pub fn new() -> VolumeEnvelope
+    {
+        return VolumeEnvelope
+        {
+            initial_volume: 0,
+            increase: false,
+            period: 0,
+            timer: 0,
+            volume: 0,
+        }
+    }
+
+    pub fn write(&mut self, value: u8)
+    {
+        self.initial_volume = (value & 0xF0) >> 4;
+        self.increase = (value & 0x08) == 0x08;
+        self.period = value & 0x07;
+    }
+
+    pub fn dac_enabled(&self) -> bool
+    {
+        return self.initial_volume != 0 || self.increase;
+    }
+
+    pub fn trigger(&mut self)
+    {
+        self.volume = self.initial_volume;
+        self.timer = map_period(self.period);
+    }
+
+    pub fn clock(&mut self)
+    {
+        //A timer of 0 means the envelope was written but never triggered
+        if self.period == 0 || self.timer == 0
+        {
+            return;
+        }
+        self.timer -= 1;
+        if self.timer == 0
+        {
+            self.timer = map_period(self.period);
+            if self.increase && self.volume < 15
+            {
+                self.volume += 1;
+            }
+            else if !self.increase && self.volume > 0
+            {
+                self.volume -= 1;
+            }
+        }
+    }
+}
+
+//A period of 0 is treated as 8 by the envelope and sweep timers
+fn map_period(period: u8) -> u8
+{
+    if period == 0
+    {
+        return 8;
+    }
+    return period;
+}
+
+pub struct FrequencySweep
+{
+    pub period: u8,
+    pub negate: bool,
+    pub shift: u8,
+    pub timer: u8,
+    pub enabled: bool,
+    pub shadow_frequency: u16,
+}
+
+impl FrequencySweep
+{
+    pub fn new() -> FrequencySweep
+    {
+        return FrequencySweep
+        {
+            period: 0,
+            negate: false,
+            shift: 0,
+            timer: 0,
+            enabled: false,
+            shadow_frequency: 0,
+        }
+    }
+
+    pub fn write(&mut self, value: u8)
+    {
+        self.period = (value & 0x70) >> 4;
+        self.negate = (value & 0x08) == 0x08;
+        self.shift = value & 0x07;
+    }
+
+    //Returns None when the new frequency overflows past 2047
+    pub fn calculate_frequency(&self) -> Option<u16>
+    {
+        let offset = self.shadow_frequency >> self.shift;
+        let new_frequency = if self.negate
+        {
+            self.shadow_frequency - offset
+        }
+        else
+        {
+            self.shadow_frequency + offset
+        };
+        if new_frequency > 2047
+        {
+            return None;
+        }
+        return Some(new_frequency);
+    }
+}
+
+pub struct SquareChannel
+{
+    pub enabled: bool,
+    pub duty: u8,
+    pub duty_position: u8,
+    pub frequency: u16,
+    pub timer: u16,
+    pub length: LengthCounter,
+    pub envelope: VolumeEnvelope,
+    pub sweep: FrequencySweep,
+}
+
+impl SquareChannel
+{
+    pub fn new() -> SquareChannel
+    {
+        return SquareChannel
+        {
+            enabled: false,
+            duty: 0,
+            duty_position: 0,
+            frequency: 0,
+            timer: 0,
+            length: LengthCounter::new(64),
+            envelope: VolumeEnvelope::new(),
+            sweep: FrequencySweep::new(),
+        }
+    }
+
+    pub fn write_length_duty(&mut self, value: u8)
+    {
+        self.duty = (value & 0xC0) >> 6;
+        self.length.load(value & 0x3F);
+    }
+
+    pub fn write_envelope(&mut self, value: u8)
+    {
+        self.envelope.write(value);
+        if !self.envelope.dac_enabled()
+        {
+            self.enabled = false;
+        }
+    }
+
+    pub fn write_frequency_low(&mut self, value: u8)
+    {
+        self.frequency = (self.frequency & 0x0700) | value as u16;
+    }
+
+    pub fn write_frequency_high(&mut self, value: u8, sweep_enabled: bool)
+    {
+        self.frequency = (self.frequency & 0x00FF) | ((value as u16 & 0x07) << 8);
+        self.length.enabled = (value & 0x40) == 0x40;
+        if (value & 0x80) == 0x80
+        {
+            self.trigger(sweep_enabled);
+        }
+    }
+
+    fn trigger(&mut self, sweep_enabled: bool)
+    {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger();
+        self.timer = (2048 - self.frequency) * 4;
+        self.envelope.trigger();
+
+        if sweep_enabled
+        {
+            self.sweep.shadow_frequency = self.frequency;
+            self.sweep.timer = map_period(self.sweep.period);
+            self.sweep.enabled = self.sweep.period != 0 || self.sweep.shift != 0;
+            if self.sweep.shift != 0 && self.sweep.calculate_frequency().is_none()
+            {
+                self.enabled = false;
+            }
+        }
+    }
+
+    pub fn clock_length(&mut self)
+    {
+        if self.length.clock()
+        {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_sweep(&mut self)
+    {
+        if self.sweep.timer > 0
+        {
+            self.sweep.timer -= 1;
+        }
+        if self.sweep.timer != 0
+        {
+            return;
+        }
+        self.sweep.timer = map_period(self.sweep.period);
+        if !self.sweep.enabled || self.sweep.period == 0
+        {
+            return;
+        }
+        match self.sweep.calculate_frequency()
+        {
+            Some(new_frequency) =>
+            {
+                if self.sweep.shift != 0
+                {
+                    self.sweep.shadow_frequency = new_frequency;
+                    self.frequency = new_frequency;
+                    if self.sweep.calculate_frequency().is_none()
+                    {
+                        self.enabled = false;
+                    }
+                }
+            },
+            None => self.enabled = false,
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u16)
+    {
+        let mut remaining = cycles;
+        while remaining > 0
+        {
+            if self.timer > remaining
+            {
+                self.timer -= remaining;
+                return;
+            }
+            remaining -= self.timer;
+            self.timer = (2048 - self.frequency) * 4;
+            self.duty_position = (self.duty_position + 1) % 8;
+        }
+    }
+
+    pub fn output(&self) -> u8
+    {
+        if !self.enabled
+        {
+            return 0;
+        }
+        return DUTY_PATTERNS[self.duty as usize][self.duty_position as usize] * self.envelope.volume;
+    }
+}
+
+pub struct WaveChannel
+{
+    pub enabled: bool,
+    pub dac_enabled: bool,
+    pub volume_code: u8,
+    pub frequency: u16,
+    pub timer: u16,
+    pub position: u8,
+    pub wave_ram: Vec<u8>,
+    pub length: LengthCounter,
+}
+
+impl WaveChannel
+{
+    pub fn new() -> WaveChannel
+    {
+        return WaveChannel
+        {
+            enabled: false,
+            dac_enabled: false,
+            volume_code: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            wave_ram: vec![0; 16],
+            length: LengthCounter::new(256),
+        }
+    }
+
+    pub fn write_dac(&mut self, value: u8)
+    {
+        self.dac_enabled = (value & 0x80) == 0x80;
+        if !self.dac_enabled
+        {
+            self.enabled = false;
+        }
+    }
+
+    pub fn write_volume(&mut self, value: u8)
+    {
+        self.volume_code = (value & 0x60) >> 5;
+    }
+
+    pub fn write_frequency_low(&mut self, value: u8)
+    {
+        self.frequency = (self.frequency & 0x0700) | value as u16;
+    }
+
+    pub fn write_frequency_high(&mut self, value: u8)
+    {
+        self.frequency = (self.frequency & 0x00FF) | ((value as u16 & 0x07) << 8);
+        self.length.enabled = (value & 0x40) == 0x40;
+        if (value & 0x80) == 0x80
+        {
+            self.enabled = self.dac_enabled;
+            self.length.trigger();
+            self.timer = (2048 - self.frequency) * 2;
+            self.position = 0;
+        }
+    }
+
+    pub fn clock_length(&mut self)
+    {
+        if self.length.clock()
+        {
+            self.enabled = false;
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u16)
+    {
+        let mut remaining = cycles;
+        while remaining > 0
+        {
+            if self.timer > remaining
+            {
+                self.timer -= remaining;
+                return;
+            }
+            remaining -= self.timer;
+            self.timer = (2048 - self.frequency) * 2;
+            self.position = (self.position + 1) % 32;
+        }
+    }
+
+    pub fn output(&self) -> u8
+    {
+        if !self.enabled
+        {
+            return 0;
+        }
+        let sample_byte = self.wave_ram[(self.position / 2) as usize];
+        let sample = if self.position % 2 == 0
+        {
+            sample_byte >> 4
+        }
+        else
+        {
+            sample_byte & 0x0F
+        };
+        match self.volume_code
+        {
+            0 => return 0,
+            1 => return sample,
+            2 => return sample >> 1,
+            _ => return sample >> 2,
+        }
+    }
+}
+
+pub struct NoiseChannel
+{
+    pub enabled: bool,
+    pub clock_shift: u8,
+    pub width_mode: bool,
+    pub divisor_code: u8,
+    pub timer: u32,
+    pub lfsr: u16,
+    pub length: LengthCounter,
+    pub envelope: VolumeEnvelope,
+}
+
+impl NoiseChannel
+{
+    pub fn new() -> NoiseChannel
+    {
+        return NoiseChannel
+        {
+            enabled: false,
+            clock_shift: 0,
+            width_mode: false,
+            divisor_code: 0,
+            timer: 0,
+            lfsr: 0x7FFF,
+            length: LengthCounter::new(64),
+            envelope: VolumeEnvelope::new(),
+        }
+    }
+
+    pub fn write_length(&mut self, value: u8)
+    {
+        self.length.load(value & 0x3F);
+    }
+
+    pub fn write_envelope(&mut self, value: u8)
+    {
+        self.envelope.write(value);
+        if !self.envelope.dac_enabled()
+        {
+            self.enabled = false;
+        }
+    }
+
+    pub fn write_polynomial(&mut self, value: u8)
+    {
+        self.clock_shift = (value & 0xF0) >> 4;
+        self.width_mode = (value & 0x08) == 0x08;
+        self.divisor_code = value & 0x07;
+    }
+
+    pub fn write_control(&mut self, value: u8)
+    {
+        self.length.enabled = (value & 0x40) == 0x40;
+        if (value & 0x80) == 0x80
+        {
+            self.enabled = self.envelope.dac_enabled();
+            self.length.trigger();
+            self.timer = self.period();
+            self.envelope.trigger();
+            self.lfsr = 0x7FFF;
+        }
+    }
+
+    //Up to 112 << 15, too large for a u16
+    fn period(&self) -> u32
+    {
+        return NOISE_DIVISORS[self.divisor_code as usize] << self.clock_shift;
+    }
+
+    pub fn clock_length(&mut self)
+    {
+        if self.length.clock()
+        {
+            self.enabled = false;
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u16)
+    {
+        //Shifts 14 and 15 stop the LFSR from being clocked at all
+        if self.clock_shift >= 14
+        {
+            return;
+        }
+        let mut remaining = cycles as u32;
+        while remaining > 0
+        {
+            if self.timer > remaining
+            {
+                self.timer -= remaining;
+                return;
+            }
+            remaining -= self.timer;
+            self.timer = self.period();
+            let xor_bit = (self.lfsr & 0x01) ^ ((self.lfsr >> 1) & 0x01);
+            self.lfsr = (self.lfsr >> 1) | (xor_bit << 14);
+            if self.width_mode
+            {
+                self.lfsr = (self.lfsr & !0x40) | (xor_bit << 6);
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8
+    {
+        if !self.enabled || (self.lfsr & 0x01) == 0x01
+        {
+            return 0;
+        }
+        return self.envelope.volume;
+    }
+}
+
+#[cfg(test)]
+mod channel_tests
+{
+    use apu::channels::*;
+
+    #[test]
+    fn length_counter_test()
+    {
+        let mut length = LengthCounter::new(64);
+        length.load(60);
+        assert_eq!(length.counter, 4);
+        assert!(!length.clock());
+        length.enabled = true;
+        assert!(!length.clock());
+        assert!(!length.clock());
+        assert!(!length.clock());
+        assert!(length.clock());
+        assert!(!length.clock());
+        length.trigger();
+        assert_eq!(length.counter, 64);
+    }
+
+    #[test]
+    fn volume_envelope_test()
+    {
+        let mut envelope = VolumeEnvelope::new();
+        envelope.write(0xF2);
+        assert!(envelope.dac_enabled());
+        envelope.trigger();
+        assert_eq!(envelope.volume, 15);
+        envelope.clock();
+        assert_eq!(envelope.volume, 15);
+        envelope.clock();
+        assert_eq!(envelope.volume, 14);
+
+        envelope.write(0x09);
+        assert!(envelope.dac_enabled());
+        envelope.trigger();
+        assert_eq!(envelope.volume, 0);
+        envelope.clock();
+        assert_eq!(envelope.volume, 1);
+
+        envelope.write(0x00);
+        assert!(!envelope.dac_enabled());
+    }
+
+    #[test]
+    fn square_duty_test()
+    {
+        let mut channel = SquareChannel::new();
+        channel.write_length_duty(0x80);
+        channel.write_envelope(0xF0);
+        channel.write_frequency_low(0xFF);
+        channel.write_frequency_high(0x87, false);
+        assert!(channel.enabled);
+        let mut outputs = Vec::new();
+        for _ in 0..8
+        {
+            outputs.push(channel.output());
+            channel.tick(4);
+        }
+        assert_eq!(outputs, vec![15, 0, 0, 0, 0, 15, 15, 15]);
+    }
+
+    #[test]
+    fn sweep_overflow_test()
+    {
+        let mut channel = SquareChannel::new();
+        channel.sweep.write(0x11);
+        channel.write_envelope(0xF0);
+        channel.write_frequency_low(0x00);
+        channel.write_frequency_high(0x84, true);
+        assert!(channel.enabled);
+        channel.clock_sweep();
+        assert_eq!(channel.frequency, 0x600);
+        assert!(!channel.enabled);
+
+        channel.sweep.write(0x19);
+        channel.write_frequency_high(0x84, true);
+        channel.clock_sweep();
+        assert_eq!(channel.frequency, 0x200);
+        assert!(channel.enabled);
+    }
+
+    #[test]
+    fn wave_output_test()
+    {
+        let mut channel = WaveChannel::new();
+        channel.wave_ram[0] = 0xA5;
+        channel.write_dac(0x80);
+        channel.write_volume(0x20);
+        channel.write_frequency_low(0xFF);
+        channel.write_frequency_high(0x87);
+        assert_eq!(channel.output(), 0x0A);
+        channel.tick(2);
+        assert_eq!(channel.output(), 0x05);
+        channel.write_volume(0x40);
+        assert_eq!(channel.output(), 0x02);
+        channel.write_dac(0x00);
+        assert_eq!(channel.output(), 0);
+    }
+
+    #[test]
+    fn noise_lfsr_test()
+    {
+        let mut channel = NoiseChannel::new();
+        channel.write_envelope(0xF0);
+        channel.write_polynomial(0x08);
+        channel.write_control(0x80);
+        assert_eq!(channel.lfsr, 0x7FFF);
+        assert_eq!(channel.output(), 0);
+        channel.tick(8);
+        assert_eq!(channel.lfsr, 0x3FBF);
+        channel.tick(8);
+        assert_eq!(channel.lfsr, 0x1F9F);
+        for _ in 0..5
+        {
+            channel.tick(8);
+        }
+        assert_eq!(channel.lfsr & 0x01, 0x00);
+        assert_eq!(channel.output(), 15);
+    }
+
+    #[test]
+    fn noise_large_shift_test()
+    {
+        let mut channel = NoiseChannel::new();
+        channel.write_envelope(0xF0);
+        channel.write_polynomial(0xD1);
+        channel.write_control(0x80);
+        assert_eq!(channel.timer, 16 << 13);
+        channel.tick(16);
+        assert_eq!(channel.timer, (16 << 13) - 16);
+
+        channel.write_polynomial(0xF1);
+        channel.write_control(0x80);
+        channel.tick(16);
+        assert_eq!(channel.lfsr, 0x7FFF);
+    }
+
+    #[test]
+    fn untriggered_envelope_test()
+    {
+        let mut envelope = VolumeEnvelope::new();
+        envelope.write(0xF3);
+        for _ in 0..8
+        {
+            envelope.clock();
+        }
+        assert_eq!(envelope.volume, 0);
+    }
+}
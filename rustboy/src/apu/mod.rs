@@ -0,0 +1,4 @@
+pub mod apu;
+pub mod channels;
+pub mod playback;
+pub mod wav;
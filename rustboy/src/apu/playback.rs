@@ -0,0 +1,121 @@
+//Fixed size buffer of interleaved samples shared between the emulation loop and the audio callback
+pub struct SampleRingBuffer
+{
+    samples: Vec<f32>,
+    read_index: usize,
+    length: usize,
+}
+
+impl SampleRingBuffer
+{
+    pub fn new(capacity: usize) -> SampleRingBuffer
+    {
+        return SampleRingBuffer
+        {
+            samples: vec![0.0; capacity],
+            read_index: 0,
+            length: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize
+    {
+        return self.length;
+    }
+
+    pub fn capacity(&self) -> usize
+    {
+        return self.samples.len();
+    }
+
+    //Samples that do not fit are dropped, returns how many were stored
+    pub fn push(&mut self, samples: &[f32]) -> usize
+    {
+        let capacity = self.capacity();
+        let count = samples.len().min(capacity - self.length);
+        for i in 0..count
+        {
+            let index = (self.read_index + self.length) % capacity;
+            self.samples[index] = samples[i];
+            self.length += 1;
+        }
+        return count;
+    }
+
+    //Fills the output, padding with silence on underrun, returns how many samples were real
+    pub fn pop(&mut self, output: &mut [f32]) -> usize
+    {
+        let capacity = self.capacity();
+        let count = output.len().min(self.length);
+        for i in 0..count
+        {
+            output[i] = self.samples[self.read_index];
+            self.read_index = (self.read_index + 1) % capacity;
+            self.length -= 1;
+        }
+        for i in count..output.len()
+        {
+            output[i] = 0.0;
+        }
+        return count;
+    }
+}
+
+//Nudges the emulated sample rate so the buffer hovers around half full.
+//Output is pitched by at most max_delta, too little to hear.
+pub struct RateControl
+{
+    pub host_rate: f64,
+    pub max_delta: f64,
+}
+
+impl RateControl
+{
+    pub fn new(host_rate: f64) -> RateControl
+    {
+        return RateControl
+        {
+            host_rate: host_rate,
+            max_delta: 0.005,
+        }
+    }
+
+    pub fn sample_rate(&self, buffered: usize, capacity: usize) -> f64
+    {
+        let fill = buffered.min(capacity) as f64 / capacity as f64;
+        return self.host_rate * (1.0 + self.max_delta * (1.0 - 2.0 * fill));
+    }
+}
+
+#[cfg(test)]
+mod playback_tests
+{
+    use apu::playback::*;
+
+    #[test]
+    fn ring_buffer_test()
+    {
+        let mut buffer = SampleRingBuffer::new(4);
+        assert_eq!(buffer.push(&[1.0, 2.0, 3.0]), 3);
+        let mut output = [0.0; 2];
+        assert_eq!(buffer.pop(&mut output), 2);
+        assert_eq!(output, [1.0, 2.0]);
+        assert_eq!(buffer.push(&[4.0, 5.0, 6.0, 7.0]), 3);
+        assert_eq!(buffer.len(), 4);
+
+        let mut output = [9.0; 6];
+        assert_eq!(buffer.pop(&mut output), 4);
+        assert_eq!(output, [3.0, 4.0, 5.0, 6.0, 0.0, 0.0]);
+        assert_eq!(buffer.len(), 0);
+    }
+
+    #[test]
+    fn rate_control_test()
+    {
+        let control = RateControl::new(48000.0);
+        assert_eq!(control.sample_rate(512, 1024), 48000.0);
+        assert!((control.sample_rate(0, 1024) - 48240.0).abs() < 0.001);
+        assert!((control.sample_rate(1024, 1024) - 47760.0).abs() < 0.001);
+        assert!(control.sample_rate(768, 1024) < 48000.0);
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufWriter, SeekFrom};
+
+//16-bit PCM stereo, the sizes in the header are filled in by finish
+pub struct WavWriter<W: Write + Seek>
+{
+    output: W,
+    pub sample_rate: u32,
+    pub data_bytes: u32,
+}
+
+impl WavWriter<BufWriter<File>>
+{
+    pub fn create(file_name: &str, sample_rate: u32) -> io::Result<WavWriter<BufWriter<File>>>
+    {
+        let file = File::create(file_name)?;
+        return WavWriter::new(BufWriter::new(file), sample_rate);
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W>
+{
+    pub fn new(output: W, sample_rate: u32) -> io::Result<WavWriter<W>>
+    {
+        let mut writer = WavWriter
+        {
+            output: output,
+            sample_rate: sample_rate,
+            data_bytes: 0,
+        };
+        writer.write_header()?;
+        return Ok(writer);
+    }
+
+    fn write_header(&mut self) -> io::Result<()>
+    {
+        let channels: u16 = 2;
+        let bits_per_sample: u16 = 16;
+        let block_align = channels * bits_per_sample / 8;
+        let mut header = Vec::new();
+        header.extend_from_slice(b"RIFF");
+        push_u32(&mut header, 36 + self.data_bytes);
+        header.extend_from_slice(b"WAVE");
+        header.extend_from_slice(b"fmt ");
+        push_u32(&mut header, 16);
+        push_u16(&mut header, 1);
+        push_u16(&mut header, channels);
+        push_u32(&mut header, self.sample_rate);
+        push_u32(&mut header, self.sample_rate * block_align as u32);
+        push_u16(&mut header, block_align);
+        push_u16(&mut header, bits_per_sample);
+        header.extend_from_slice(b"data");
+        push_u32(&mut header, self.data_bytes);
+        return self.output.write_all(&header);
+    }
+
+    //Takes interleaved left/right samples in -1.0..1.0
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()>
+    {
+        let mut data = Vec::with_capacity(samples.len() * 2);
+        for sample in samples
+        {
+            let value = (sample.max(-1.0).min(1.0) * 32767.0).round() as i16;
+            push_u16(&mut data, value as u16);
+        }
+        self.output.write_all(&data)?;
+        self.data_bytes += data.len() as u32;
+        return Ok(());
+    }
+
+    pub fn finish(mut self) -> io::Result<W>
+    {
+        self.output.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.output.seek(SeekFrom::End(0))?;
+        self.output.flush()?;
+        return Ok(self.output);
+    }
+}
+
+fn push_u16(buffer: &mut Vec<u8>, value: u16)
+{
+    buffer.push((value & 0xFF) as u8);
+    buffer.push((value >> 8) as u8);
+}
+
+fn push_u32(buffer: &mut Vec<u8>, value: u32)
+{
+    push_u16(buffer, (value & 0xFFFF) as u16);
+    push_u16(buffer, (value >> 16) as u16);
+}
+
+#[cfg(test)]
+mod wav_tests
+{
+    use apu::wav::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn write_wav_test()
+    {
+        let mut writer = WavWriter::new(Cursor::new(Vec::new()), 48000).unwrap();
+        writer.write_samples(&[0.0, 1.0, -1.0, 0.5]).unwrap();
+        writer.write_samples(&[2.0, -2.0]).unwrap();
+        let bytes = writer.finish().unwrap().into_inner();
+
+        assert_eq!(bytes.len(), 44 + 12);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[4..8], &[48, 0, 0, 0]);
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(&bytes[22..24], &[2, 0]);
+        assert_eq!(&bytes[24..28], &[0x80, 0xBB, 0x00, 0x00]);
+        assert_eq!(&bytes[34..36], &[16, 0]);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(&bytes[40..44], &[12, 0, 0, 0]);
+        assert_eq!(&bytes[44..], &[0x00, 0x00, 0xFF, 0x7F, 0x01, 0x80, 0x00, 0x40, 0xFF, 0x7F, 0x01, 0x80]);
+    }
+}
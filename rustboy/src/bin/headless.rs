@@ -0,0 +1,276 @@
+extern crate rustboy;
+
+use rustboy::GameBoy;
+use rustboy::model::Model;
+use rustboy::apu::wav::WavWriter;
+use std::env;
+use std::process;
+
+static USAGE: &str = "Usage: rustboy-headless <rom> [--frames <n> | --cycles <n>] [--screenshot <frame>]... [--output <prefix>] [--wav <file>] [--sample-rate <hz>] [--patch <file>] [--boot-rom <file>] [--model <dmg|mgb|sgb|cgb>] [--pixel-fifo]";
+
+pub struct HeadlessOptions
+{
+    pub rom_file: String,
+    pub frames: Option<u64>,
+    pub cycles: Option<u64>,
+    pub screenshot_frames: Vec<u64>,
+    pub output_prefix: String,
+    pub wav_file: Option<String>,
+    pub sample_rate: u32,
+    pub patch_file: Option<String>,
+    pub boot_rom_file: Option<String>,
+    pub model: Model,
+    pub pixel_fifo: bool,
+}
+
+impl HeadlessOptions
+{
+    pub fn parse(args: &[String]) -> Result<HeadlessOptions, String>
+    {
+        let mut options = HeadlessOptions
+        {
+            rom_file: String::new(),
+            frames: None,
+            cycles: None,
+            screenshot_frames: Vec::new(),
+            output_prefix: String::from("screenshot"),
+            wav_file: None,
+            sample_rate: 44100,
+            patch_file: None,
+            boot_rom_file: None,
+            model: Model::DMG,
+            pixel_fifo: false,
+        };
+
+        let mut index = 0;
+        while index < args.len()
+        {
+            let arg = &args[index];
+            match arg.as_ref()
+            {
+                "--pixel-fifo" =>
+                {
+                    options.pixel_fifo = true;
+                    index += 1;
+                },
+                "--frames" | "--cycles" | "--screenshot" | "--output" | "--wav" | "--sample-rate" | "--patch" | "--boot-rom" | "--model" =>
+                {
+                    if index + 1 >= args.len()
+                    {
+                        return Err(format!("Missing value for {}", arg));
+                    }
+                    let value = &args[index + 1];
+                    match arg.as_ref()
+                    {
+                        "--frames" => options.frames = Some(parse_number(arg, value)?),
+                        "--cycles" => options.cycles = Some(parse_number(arg, value)?),
+                        "--screenshot" => options.screenshot_frames.push(parse_number(arg, value)?),
+                        "--wav" => options.wav_file = Some(value.clone()),
+                        "--patch" => options.patch_file = Some(value.clone()),
+                        "--boot-rom" => options.boot_rom_file = Some(value.clone()),
+                        "--model" => options.model = Model::parse(value).ok_or(format!("Unknown model: {}", value))?,
+                        "--sample-rate" => options.sample_rate = parse_number(arg, value)? as u32,
+                        _ => options.output_prefix = value.clone(),
+                    }
+                    index += 2;
+                },
+                _ =>
+                {
+                    if !options.rom_file.is_empty()
+                    {
+                        return Err(format!("Unexpected argument {}", arg));
+                    }
+                    options.rom_file = arg.clone();
+                    index += 1;
+                },
+            }
+        }
+
+        if options.rom_file.is_empty()
+        {
+            return Err(String::from("No ROM file given"));
+        }
+        if options.frames.is_some() && options.cycles.is_some()
+        {
+            return Err(String::from("Only one of --frames and --cycles can be given"));
+        }
+        if options.frames.is_none() && options.cycles.is_none()
+        {
+            options.frames = Some(60);
+        }
+        if options.sample_rate == 0
+        {
+            return Err(String::from("Sample rate must be greater than 0"));
+        }
+        return Ok(options);
+    }
+
+    fn finished(&self, gameboy: &GameBoy) -> bool
+    {
+        match (self.frames, self.cycles)
+        {
+            (Some(frames), _) => gameboy.frame_count >= frames,
+            (_, Some(cycles)) => gameboy.cycle_count >= cycles,
+            _ => true,
+        }
+    }
+}
+
+fn parse_number(arg: &str, value: &str) -> Result<u64, String>
+{
+    return value.parse::<u64>().map_err(|_| format!("Invalid value for {}: {}", arg, value));
+}
+
+fn save_screenshot(gameboy: &GameBoy, prefix: &str)
+{
+    let file_name = format!("{}_{}.png", prefix, gameboy.frame_count);
+    if let Err(error) = gameboy.framebuffer().save(&file_name)
+    {
+        eprintln!("Could not write {}: {}", file_name, error);
+        process::exit(1);
+    }
+}
+
+fn main()
+{
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match HeadlessOptions::parse(&args)
+    {
+        Ok(options) => options,
+        Err(message) =>
+        {
+            eprintln!("{}", message);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        },
+    };
+
+    let mut gameboy = GameBoy::with_model(options.model);
+    if options.pixel_fifo
+    {
+        gameboy.use_pixel_fifo();
+    }
+    if let Some(ref boot_rom_file) = options.boot_rom_file
+    {
+        if let Err(error) = gameboy.set_boot_rom(boot_rom_file)
+        {
+            eprintln!("Could not load boot ROM {}: {}", boot_rom_file, error);
+            process::exit(1);
+        }
+    }
+    if let Err(error) = gameboy.load_rom_with_patch(&options.rom_file, options.patch_file.as_ref().map(|file_name| file_name.as_str()))
+    {
+        eprintln!("Could not load {}: {}", options.rom_file, error);
+        process::exit(1);
+    }
+    if let Some(ref patch_file) = gameboy.system_data.mmu.patch_file
+    {
+        println!("Applied patch {}", patch_file);
+    }
+
+    let mut wav_writer = match options.wav_file
+    {
+        Some(ref file_name) =>
+        {
+            gameboy.set_audio_sample_rate(options.sample_rate);
+            match WavWriter::create(file_name, options.sample_rate)
+            {
+                Ok(writer) => Some(writer),
+                Err(error) =>
+                {
+                    eprintln!("Could not write {}: {}", file_name, error);
+                    process::exit(1);
+                },
+            }
+        },
+        None => None,
+    };
+
+    while !options.finished(&gameboy)
+    {
+        gameboy.step_instruction();
+        if let Some(ref mut writer) = wav_writer
+        {
+            if let Err(error) = writer.write_samples(&gameboy.take_audio_samples())
+            {
+                eprintln!("Could not write audio: {}", error);
+                process::exit(1);
+            }
+        }
+        if gameboy.poll_frame() && options.screenshot_frames.contains(&gameboy.frame_count)
+        {
+            save_screenshot(&gameboy, &options.output_prefix);
+        }
+    }
+
+    //Always keep the final frame when no specific frames were asked for
+    if options.screenshot_frames.is_empty()
+    {
+        save_screenshot(&gameboy, &options.output_prefix);
+    }
+
+    gameboy.save_battery();
+
+    if let Some(writer) = wav_writer
+    {
+        if let Err(error) = writer.finish()
+        {
+            eprintln!("Could not write audio: {}", error);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod headless_tests
+{
+    use HeadlessOptions;
+    use rustboy::model::Model;
+
+    fn to_args(args: &[&str]) -> Vec<String>
+    {
+        return args.iter().map(|arg| String::from(*arg)).collect();
+    }
+
+    #[test]
+    fn parse_options_test()
+    {
+        let options = HeadlessOptions::parse(&to_args(&["game.gb", "--frames", "120", "--screenshot", "30", "--screenshot", "120", "--output", "out/game"])).unwrap();
+        assert_eq!(options.rom_file, "game.gb");
+        assert_eq!(options.frames, Some(120));
+        assert_eq!(options.cycles, None);
+        assert_eq!(options.screenshot_frames, vec![30, 120]);
+        assert_eq!(options.output_prefix, "out/game");
+        assert_eq!(options.wav_file, None);
+        assert_eq!(options.sample_rate, 44100);
+
+        let options = HeadlessOptions::parse(&to_args(&["game.gb", "--wav", "out/game.wav", "--sample-rate", "48000", "--patch", "fix.ips", "--boot-rom", "dmg_boot.bin"])).unwrap();
+        assert_eq!(options.wav_file, Some(String::from("out/game.wav")));
+        assert_eq!(options.sample_rate, 48000);
+        assert_eq!(options.patch_file, Some(String::from("fix.ips")));
+        assert_eq!(options.boot_rom_file, Some(String::from("dmg_boot.bin")));
+
+        let options = HeadlessOptions::parse(&to_args(&["game.gb", "--model", "sgb", "--pixel-fifo"])).unwrap();
+        assert_eq!(options.model, Model::SGB);
+        assert!(options.pixel_fifo);
+
+        let options = HeadlessOptions::parse(&to_args(&["--cycles", "70224", "game.gb"])).unwrap();
+        assert_eq!(options.frames, None);
+        assert_eq!(options.cycles, Some(70224));
+
+        let options = HeadlessOptions::parse(&to_args(&["game.gb"])).unwrap();
+        assert_eq!(options.frames, Some(60));
+    }
+
+    #[test]
+    fn parse_bad_options_test()
+    {
+        assert!(HeadlessOptions::parse(&to_args(&[])).is_err());
+        assert!(HeadlessOptions::parse(&to_args(&["game.gb", "--frames"])).is_err());
+        assert!(HeadlessOptions::parse(&to_args(&["game.gb", "--frames", "ten"])).is_err());
+        assert!(HeadlessOptions::parse(&to_args(&["game.gb", "--frames", "1", "--cycles", "1"])).is_err());
+        assert!(HeadlessOptions::parse(&to_args(&["game.gb", "other.gb"])).is_err());
+        assert!(HeadlessOptions::parse(&to_args(&["game.gb", "--sample-rate", "0"])).is_err());
+        assert!(HeadlessOptions::parse(&to_args(&["game.gb", "--model", "gba"])).is_err());
+    }
+}
@@ -0,0 +1,48 @@
+extern crate rustboy;
+
+use rustboy::harness::mooneye::run_mooneye_directory;
+use rustboy::harness::TestResult;
+use std::env;
+use std::process;
+
+fn main()
+{
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2
+    {
+        eprintln!("Usage: rustboy-mooneye <rom directory> [timeout seconds]");
+        process::exit(1);
+    }
+    let timeout_seconds = match args.get(2)
+    {
+        Some(value) => value.parse::<u64>().unwrap_or_else(|_|
+        {
+            eprintln!("Invalid timeout: {}", value);
+            process::exit(1);
+        }),
+        None => 10,
+    };
+
+    let results = run_mooneye_directory(&args[1], timeout_seconds).unwrap_or_else(|error|
+    {
+        eprintln!("Could not read {}: {}", args[1], error);
+        process::exit(1);
+    });
+
+    let mut passed = 0;
+    for &(ref rom_name, ref result) in results.iter()
+    {
+        match *result
+        {
+            TestResult::Passed => { println!("PASS     {}", rom_name); passed += 1; },
+            TestResult::Failed => println!("FAIL     {}", rom_name),
+            TestResult::Timeout => println!("TIMEOUT  {}", rom_name),
+        }
+    }
+    println!("{}/{} passed", passed, results.len());
+
+    if passed != results.len()
+    {
+        process::exit(1);
+    }
+}
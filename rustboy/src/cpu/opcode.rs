@@ -98,6 +98,12 @@ pub fn parse_opcode(system_data_original: &mut SystemData, registers_original: &
 
     system_data.cycles = cycle_parse(opcode);
 
+    //LD B,B is used as a software breakpoint by test ROMs
+    if opcode == 0x40
+    {
+        system_data.breakpoint_flag = true;
+    }
+
     match opcode
     {
 0x00 => no_operation(&mut system_data, &mut registers),
@@ -49,22 +49,22 @@ mod opcode_test
         let mut system_data : SystemData = get_system_data(&String::from("CLASSIC"));
         let mut registers : Registers = Registers::new();
 
-        registers.mapped_16_bit_register_setter(3, 0x1234);
+        registers.mapped_16_bit_register_setter(3, 0xC234);
 
         //Normal flag
         increment_hl_location(&mut system_data, &mut registers);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 1);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 1);
         assert_eq!(registers.flags, 0x00);
         //Half flag
-        system_data.mmu.mem_map[0x1234] = 0x0F;
+        system_data.mmu.mem_map[0xC234] = 0x0F;
         increment_hl_location(&mut system_data, &mut registers);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0x10);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0x10);
         assert_eq!(registers.flags, 0x20);
 
         //Zero flag
-        system_data.mmu.mem_map[0x1234] = 0xFF;
+        system_data.mmu.mem_map[0xC234] = 0xFF;
         increment_hl_location(&mut system_data, &mut registers);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0x00);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0x00);
         assert_eq!(registers.flags, 0xA0);
     }
 
@@ -817,11 +817,11 @@ mod opcode_test
         for i in 0..opcodes.len()
         {
             registers.program_counter = 0x1234;
-            registers.stack_pointer = 0x0002;
+            registers.stack_pointer = 0xC002;
             rst_jump(&mut system_data, &mut registers, opcodes[i]);
-            assert_eq!(system_data.mmu.mem_map[0x0000], 0x35);
-            assert_eq!(system_data.mmu.mem_map[0x0001], 0x12);
-            assert_eq!(registers.stack_pointer, 0x0000);
+            assert_eq!(system_data.mmu.mem_map[0xC000], 0x35);
+            assert_eq!(system_data.mmu.mem_map[0xC001], 0x12);
+            assert_eq!(registers.stack_pointer, 0xC000);
             assert_eq!(registers.program_counter, locations[i]);
 
         }
@@ -874,9 +874,9 @@ mod opcode_test
         let mut system_data : SystemData = get_system_data(&String::from("CLASSIC"));
         let mut registers : Registers = Registers::new();
         system_data.mmu.mem_map[0x0001] = 0xFF;
-        registers.mapped_16_bit_register_setter(3, 0x1234);
+        registers.mapped_16_bit_register_setter(3, 0xC234);
         load_n_to_hl_location(&mut system_data, &mut registers);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0xFF);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0xFF);
     }
 
     #[test]
@@ -922,12 +922,12 @@ mod opcode_test
         let mut system_data : SystemData = get_system_data(&String::from("CLASSIC"));
         let mut registers : Registers = Registers::new();
         let opcodes: Vec<u8> = vec![0xC6, 0xCE, 0xD6, 0xDE, 0xE6, 0xEE, 0xF6, 0xFE];
-        registers.mapped_16_bit_register_setter(3, 0x1234);
+        registers.mapped_16_bit_register_setter(3, 0xC234);
         for i in 0..opcodes.len()
         {
-            system_data.mmu.mem_map[0x1234] = 0;
+            system_data.mmu.mem_map[0xC234] = 0;
             set_bit_in_register(&mut system_data, &mut registers, opcodes[i]);
-            assert_eq!((system_data.mmu.mem_map[0x1234] >> i) & 0x01, 0x01);
+            assert_eq!((system_data.mmu.mem_map[0xC234] >> i) & 0x01, 0x01);
         }
     }
 
@@ -1026,7 +1026,7 @@ mod opcode_test
         let program_counters: Vec<usize> = vec![0x0703, 0x0501, 0x02FF, 0x00FD];    
         let opcodes: Vec<u8> = vec![0xC4, 0xCC, 0xD4, 0xDC];
         
-        registers.stack_pointer = 0x08;
+        registers.stack_pointer = 0xC008;
 
         for i in 0..flags.len()
         {
@@ -1048,7 +1048,7 @@ mod opcode_test
 
         for i in 0..8
         {
-            assert_eq!(system_data.mmu.mem_map[i], i as u8);
+            assert_eq!(system_data.mmu.mem_map[0xC000 + i], i as u8);
         }
 
     }
@@ -1252,18 +1252,18 @@ mod opcode_test
     {
         let mut system_data : SystemData = get_system_data(&String::from("CLASSIC"));
         let mut registers : Registers = Registers::new();
-        registers.mapped_16_bit_register_setter(3, 0x1234);
+        registers.mapped_16_bit_register_setter(3, 0xC234);
 
         //Zero flag test
-        system_data.mmu.mem_map[0x1234] = 0x00;
+        system_data.mmu.mem_map[0xC234] = 0x00;
         shift_right_register_logical(&mut system_data, &mut registers, 0x3E);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0x00);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0x00);
         assert_eq!(registers.flags, 0x80);
 
-        system_data.mmu.mem_map[0x1234] = 0xFF;
+        system_data.mmu.mem_map[0xC234] = 0xFF;
         shift_right_register_logical(&mut system_data, &mut registers, 0x3E);
         assert_eq!(registers.flags, 0x10);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0x7F);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0x7F);
     }
 
     #[test]
@@ -1321,27 +1321,27 @@ mod opcode_test
     fn rotate_hl_location_right_through_carry_test() {
         let mut system_data : SystemData = get_system_data(&String::from("CLASSIC"));
         let mut registers : Registers = Registers::new();
-        registers.mapped_16_bit_register_setter(3, 0x1234);
+        registers.mapped_16_bit_register_setter(3, 0xC234);
 
         //No Set
-        system_data.mmu.mem_map[0x1234] = 0x02;
+        system_data.mmu.mem_map[0xC234] = 0x02;
         registers.flags = 0x00;
         rotate_right_through_carry(&mut system_data, &mut registers, 0x1E);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0x01);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0x01);
         assert_eq!(registers.flags, 0x00);
 
         //Zero Flag
-        system_data.mmu.mem_map[0x1234] = 0x00;
+        system_data.mmu.mem_map[0xC234] = 0x00;
         registers.flags = 0x00;
         rotate_right_through_carry(&mut system_data, &mut registers, 0x1E);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0x00);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0x00);
         assert_eq!(registers.flags, 0x80);
 
         //Carry
-        system_data.mmu.mem_map[0x1234] = 0xFF;
+        system_data.mmu.mem_map[0xC234] = 0xFF;
         registers.flags = 0x10;
         rotate_right_through_carry(&mut system_data, &mut registers, 0x1E);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0xFF);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0xFF);
         assert_eq!(registers.flags, 0x10);
     }
 
@@ -1445,21 +1445,21 @@ mod opcode_test
     {
         let mut system_data : SystemData = get_system_data(&String::from("CLASSIC"));
         let mut registers : Registers = Registers::new();
-        registers.mapped_16_bit_register_setter(3, 0x1234);
+        registers.mapped_16_bit_register_setter(3, 0xC234);
         //H flag
-        system_data.mmu.mem_map[0x1234] = 0x10;
+        system_data.mmu.mem_map[0xC234] = 0x10;
         decrement_hl_location(&mut system_data, &mut registers);
         assert_eq!(registers.flags, 0x60);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0x0F);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0x0F);
         //No flag
         decrement_hl_location(&mut system_data, &mut registers);
         assert_eq!(registers.flags, 0x40);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0x0E);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0x0E);
         //Z flag
-        system_data.mmu.mem_map[0x1234] = 0x01;
+        system_data.mmu.mem_map[0xC234] = 0x01;
         decrement_hl_location(&mut system_data, &mut registers);
         assert_eq!(registers.flags, 0xC0);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0x00);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0x00);
     }
 
     #[test]
@@ -1467,10 +1467,10 @@ mod opcode_test
     {
         let mut system_data : SystemData = get_system_data(&String::from("CLASSIC"));
         let mut registers : Registers = Registers::new();
-        registers.mapped_16_bit_register_setter(2, 0x1234);
+        registers.mapped_16_bit_register_setter(2, 0xC234);
         registers.accumulator = 0xFF;
         load_de_location_with_accumulator(&mut system_data, &mut registers);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0xFF);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0xFF);
     }
 
     #[test]
@@ -1765,10 +1765,10 @@ mod opcode_test
         let mut registers : Registers = Registers::new();
         registers.stack_pointer = 0xFFEE;
         system_data.mmu.mem_map[0x0001] = 0x34;
-        system_data.mmu.mem_map[0x0002] = 0x12;
+        system_data.mmu.mem_map[0x0002] = 0xC2;
         load_stack_pointer_to_nn_address(&mut system_data, &mut registers);
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0xEE);
-        assert_eq!(system_data.mmu.mem_map[0x1235], 0xFF);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0xEE);
+        assert_eq!(system_data.mmu.mem_map[0xC235], 0xFF);
     }
 
     #[test]
@@ -1776,10 +1776,10 @@ mod opcode_test
     {
         let mut system_data : SystemData = get_system_data(&String::from("CLASSIC"));
         let mut registers : Registers = Registers::new();
-        registers.mapped_16_bit_register_setter(1, 0x1234);
+        registers.mapped_16_bit_register_setter(1, 0xC234);
         registers.accumulator = 0xFF;
         load_accumulator_to_address_at_bc(&mut system_data, &mut registers);       
-        assert_eq!(system_data.mmu.mem_map[0x1234], 0xFF);
+        assert_eq!(system_data.mmu.mem_map[0xC234], 0xFF);
     }
 
     #[test]
@@ -0,0 +1,343 @@
+use cpu::cpu::cpu_continue;
+use gpu::gpu::*;
+use gpu::gpu_registers::GPU_Registers;
+use gpu::fifo::PixelFifo;
+use system::*;
+use loader::{LoadError, read_boot_rom};
+use model::Model;
+use image::{ImageBuffer, RgbaImage};
+
+pub struct GameBoy
+{
+    pub system_data: SystemData,
+    pub registers: Registers,
+    pub gpu_registers: GPU_Registers,
+    pub oam_tile_map: TileMap,
+    pub oam_table: OAM_Table,
+    pub image: RgbaImage,
+    pub cycle_count: u64,
+    pub frame_count: u64,
+}
+
+impl GameBoy
+{
+    pub fn new() -> GameBoy
+    {
+        return GameBoy::with_model(Model::DMG);
+    }
+
+    pub fn with_model(model: Model) -> GameBoy
+    {
+        let mut gpu_registers = GPU_Registers::new();
+        gpu_registers.v_blank_draw_flag = false;
+        gpu_registers.shade_profile = model.shade_profile();
+        return GameBoy
+        {
+            system_data: system_data_for_model(model),
+            registers: Registers::new(),
+            gpu_registers: gpu_registers,
+            oam_tile_map: TileMap::new(),
+            oam_table: OAM_Table::new(),
+            image: ImageBuffer::new(160, 144),
+            cycle_count: 0,
+            frame_count: 0,
+        }
+    }
+
+    //Must be called before the ROM is loaded
+    pub fn set_boot_rom(&mut self, file_name: &str) -> Result<(), LoadError>
+    {
+        self.system_data.mmu.boot_rom = Some(read_boot_rom(file_name)?);
+        return Ok(());
+    }
+
+    //Picks up a <rom>.ips/.ups/.bps patch next to the ROM if there is one
+    pub fn load_rom(&mut self, file_name: &str) -> Result<(), LoadError>
+    {
+        return self.load_rom_with_patch(file_name, None);
+    }
+
+    pub fn load_rom_with_patch(&mut self, file_name: &str, patch_file: Option<&str>) -> Result<(), LoadError>
+    {
+        self.system_data.mmu.initialize_cartridge(file_name, patch_file)?;
+        if self.system_data.mmu.boot_rom.is_some()
+        {
+            //The boot ROM sets up the registers itself and hands over at 0x0100
+            self.registers.program_counter = 0x0000;
+        }
+        else
+        {
+            init_emulator_state(&mut self.system_data, &mut self.registers);
+        }
+        self.system_data.apu.load_registers(&self.system_data.mmu.mem_map);
+        return Ok(());
+    }
+
+    //Runs a single opcode and returns the cycles it took
+    pub fn step_instruction(&mut self) -> u8
+    {
+        let joypad_input = self.system_data.input.update_input(&self.system_data);
+        if (joypad_input & 0x0F) != 0x0F
+        {
+            let current_if = self.system_data.mmu.get_from_memory(0xFF0F, false);
+            self.system_data.mmu.set_to_memory(0xFF0F, current_if | 0x10, false);
+        }
+        self.system_data.mmu.set_to_memory(0xFF00, joypad_input, false);
+        cpu_continue(&mut self.system_data, &mut self.registers);
+        update_gpu(&mut self.system_data, &mut self.registers, &mut self.gpu_registers);
+        self.system_data.timer_tick();
+        self.system_data.serial_tick();
+        self.system_data.sound_tick();
+        self.system_data.mmu.battery_tick(self.system_data.cycles);
+        self.system_data.mmu.mapper.tick(self.system_data.cycles);
+        self.cycle_count += self.system_data.cycles as u64;
+        return self.system_data.cycles;
+    }
+
+    //Runs until the next v-blank and redraws the framebuffer
+    pub fn run_frame(&mut self)
+    {
+        while !self.poll_frame()
+        {
+            self.step_instruction();
+        }
+    }
+
+    //Redraws the framebuffer if a v-blank was reached since the last call
+    pub fn poll_frame(&mut self) -> bool
+    {
+        if !self.gpu_registers.v_blank_draw_flag
+        {
+            return false;
+        }
+        self.gpu_registers.v_blank_draw_flag = false;
+        self.draw_frame();
+        self.frame_count += 1;
+        return true;
+    }
+
+    //Switches to the dot by dot renderer, slower but shows mid-line register writes
+    pub fn use_pixel_fifo(&mut self)
+    {
+        self.gpu_registers.pixel_fifo = Some(PixelFifo::new());
+    }
+
+    fn draw_frame(&mut self)
+    {
+        if let Some(ref pixel_fifo) = self.gpu_registers.pixel_fifo
+        {
+            self.image = pixel_fifo.frame_image(&self.gpu_registers.shade_profile);
+            return;
+        }
+        self.oam_tile_map.populate_tile_map(&mut self.system_data, true, true);
+        self.oam_table.populate_oam_table(&self.system_data);
+        self.image = create_background_img(&self.gpu_registers, &mut self.system_data, &self.oam_table, &self.oam_tile_map);
+    }
+
+    pub fn framebuffer(&self) -> &RgbaImage
+    {
+        return &self.image;
+    }
+
+    pub fn set_input(&mut self, input: PlayerInput)
+    {
+        self.system_data.input = input;
+    }
+
+    //Writes battery RAM to the save file, call before exiting
+    pub fn save_battery(&mut self)
+    {
+        if let Err(error) = self.system_data.mmu.save_battery_ram()
+        {
+            println!("Could not write save file: {}", error);
+        }
+    }
+
+    //Returns the new motor state if a rumble cartridge switched it since the last call
+    pub fn poll_rumble(&mut self) -> Option<bool>
+    {
+        return self.system_data.mmu.mapper.take_rumble_event();
+    }
+
+    //Samples are interleaved left/right pairs, a rate of 0 turns audio off
+    pub fn set_audio_sample_rate(&mut self, sample_rate: u32)
+    {
+        self.system_data.apu.set_sample_rate(sample_rate as f64);
+    }
+
+    //Live playback nudges the rate by fractions of a hertz to keep the host buffer from draining or filling up
+    pub fn adjust_audio_sample_rate(&mut self, sample_rate: f64)
+    {
+        self.system_data.apu.set_sample_rate(sample_rate);
+    }
+
+    pub fn take_audio_samples(&mut self) -> Vec<f32>
+    {
+        return self.system_data.apu.take_samples();
+    }
+}
+
+pub fn init_emulator_state(system_data: &mut SystemData, registers: &mut Registers)
+{
+    registers.program_counter = 0x100;
+
+    let states: Vec<u8> = vec![0x00, 0x00, 0x00, 0x80, 0xBF, 0xF3, 0xBF, 0x3F,
+                               0x00, 0xBF, 0x7F, 0xFF, 0x9F, 0xBF, 0xFF, 0x00,
+                               0x00, 0xBF, 0x77, 0xF3, 0xF1, 0x91, 0x00, 0x00,
+                               0x00, 0xFC, 0xFF, 0xFF, 0x00, 0x00, 0x00];
+
+    let mem_locations: Vec<usize> = vec![0xFF05, 0xFF06, 0xFF07, 0xFF10, 0xFF11, 0xFF12,
+                                       0xFF14, 0xFF16, 0xFF17, 0xFF19, 0xFF1A, 0xFF1B,
+                                       0xFF1C, 0xFF1E, 0xFF20, 0xFF21, 0xFF22, 0xFF23,
+                                       0xFF24, 0xFF25, 0xFF26, 0xFF40, 0xFF42, 0xFF43,
+                                       0xFF45, 0xFF47, 0xFF48, 0xFF49, 0xFF4A, 0xFF4B, 0xFFFF];
+
+    for i in 0..states.len()
+    {
+        system_data.mmu.set_to_memory(mem_locations[i], states[i] ,false);
+    }
+    for (location, value) in system_data.model.post_boot_io()
+    {
+        system_data.mmu.set_to_memory(location, value, false);
+    }
+
+    let cgb_game = system_data.mmu.header.as_ref().map_or(false, |header| (header.cgb_flag & 0x80) == 0x80);
+    let register_states = system_data.model.post_boot_registers(cgb_game);
+    for i in 0..register_states.len()
+    {
+        registers.mapped_16_bit_register_setter(i as u8, register_states[i]);
+    }
+}
+
+#[cfg(test)]
+mod gameboy_tests
+{
+    use gameboy::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn step_instruction_test()
+    {
+        let mut gameboy = GameBoy::new();
+        init_emulator_state(&mut gameboy.system_data, &mut gameboy.registers);
+        assert_eq!(gameboy.registers.program_counter, 0x100);
+        let cycles = gameboy.step_instruction();
+        assert_eq!(cycles, 4);
+        assert_eq!(gameboy.registers.program_counter, 0x101);
+        assert_eq!(gameboy.cycle_count, 4);
+    }
+
+    #[test]
+    fn run_frame_test()
+    {
+        let mut gameboy = GameBoy::new();
+        init_emulator_state(&mut gameboy.system_data, &mut gameboy.registers);
+        gameboy.run_frame();
+        assert!(!gameboy.gpu_registers.v_blank_draw_flag);
+        assert_eq!(gameboy.system_data.mmu.mem_map[0xFF44], 144);
+        assert_eq!(gameboy.framebuffer().dimensions(), (160, 144));
+        assert_eq!(gameboy.frame_count, 1);
+        assert_eq!(gameboy.cycle_count, 144 * 456);
+    }
+
+    #[test]
+    fn pixel_fifo_frame_test()
+    {
+        let mut gameboy = GameBoy::new();
+        gameboy.use_pixel_fifo();
+        init_emulator_state(&mut gameboy.system_data, &mut gameboy.registers);
+        gameboy.system_data.mmu.mem_map[0xFF47] = 0xFF;
+        gameboy.run_frame();
+        assert_eq!(gameboy.frame_count, 1);
+        assert_eq!(gameboy.cycle_count, 144 * 456);
+        assert_eq!(*gameboy.framebuffer().get_pixel(80, 143), gameboy.gpu_registers.shade_profile.shade_3);
+    }
+
+    #[test]
+    fn model_post_boot_state_test()
+    {
+        let mut gameboy = GameBoy::with_model(Model::MGB);
+        assert_eq!(gameboy.system_data.model, Model::MGB);
+        init_emulator_state(&mut gameboy.system_data, &mut gameboy.registers);
+        assert_eq!(gameboy.registers.accumulator, 0xFF);
+        assert_eq!(gameboy.registers.stack_pointer, 0xFFFE);
+        assert_eq!(gameboy.system_data.mmu.mem_map[0xFF26], 0xF1);
+
+        let mut gameboy = GameBoy::with_model(Model::SGB);
+        init_emulator_state(&mut gameboy.system_data, &mut gameboy.registers);
+        assert_eq!((gameboy.registers.accumulator, gameboy.registers.flags), (0x01, 0x00));
+        assert_eq!((gameboy.registers.h_register, gameboy.registers.l_register), (0xC0, 0x60));
+        assert_eq!(gameboy.system_data.mmu.mem_map[0xFF26], 0xF0);
+
+        let mut gameboy = GameBoy::with_model(Model::CGB);
+        init_emulator_state(&mut gameboy.system_data, &mut gameboy.registers);
+        assert_eq!(gameboy.registers.accumulator, 0x11);
+        assert_eq!((gameboy.registers.d_register, gameboy.registers.e_register), (0x00, 0x08));
+        assert_eq!(gameboy.gpu_registers.shade_profile.shade_0, Model::CGB.shade_profile().shade_0);
+    }
+
+    #[test]
+    fn boot_rom_test()
+    {
+        let directory = env::temp_dir();
+        let boot_rom_file = directory.join("rustboy_boot_rom_test.bin").to_string_lossy().into_owned();
+        let rom_file = directory.join("rustboy_boot_rom_test.gb").to_string_lossy().into_owned();
+        //NOPs, then LD A,0x01 and LDH (0x50),A in the last four bytes like the real boot ROM
+        let mut boot_rom = vec![0x00; 0x100];
+        boot_rom[0xFC..0x100].copy_from_slice(&[0x3E, 0x01, 0xE0, 0x50]);
+        fs::write(&boot_rom_file, &boot_rom[0..0xFF]).unwrap();
+        let mut rom = vec![0xC3; 0x8000];
+        rom[0x0147..0x014A].copy_from_slice(&[0x00, 0x00, 0x00]);
+        fs::write(&rom_file, &rom).unwrap();
+
+        let mut gameboy = GameBoy::new();
+        assert_eq!(gameboy.set_boot_rom(&boot_rom_file), Err(LoadError::BootRomSize(0xFF)));
+        fs::write(&boot_rom_file, &boot_rom).unwrap();
+        gameboy.set_boot_rom(&boot_rom_file).unwrap();
+        gameboy.load_rom(&rom_file).unwrap();
+        assert_eq!(gameboy.registers.program_counter, 0x0000);
+        assert_eq!(gameboy.system_data.mmu.get_from_memory(0x0000, false), 0x00);
+
+        while gameboy.registers.program_counter < 0x100
+        {
+            gameboy.step_instruction();
+        }
+        assert_eq!(gameboy.registers.program_counter, 0x100);
+        assert!(gameboy.system_data.mmu.boot_rom.is_none());
+        assert_eq!(gameboy.system_data.mmu.get_from_memory(0x0000, false), 0xC3);
+        fs::remove_file(&boot_rom_file).unwrap();
+        fs::remove_file(&rom_file).unwrap();
+    }
+
+    #[test]
+    fn load_rom_run_frames_test()
+    {
+        let rom_file = env::temp_dir().join("rustboy_run_frames_test.gb").to_string_lossy().into_owned();
+        //JR -2 at the entry point spins while the PPU and APU run
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x100..0x102].copy_from_slice(&[0x18, 0xFE]);
+        fs::write(&rom_file, &rom).unwrap();
+
+        let mut gameboy = GameBoy::new();
+        gameboy.load_rom(&rom_file).unwrap();
+        for _ in 0..5
+        {
+            gameboy.run_frame();
+        }
+        assert_eq!(gameboy.frame_count, 5);
+        assert_eq!(gameboy.registers.program_counter, 0x100);
+        fs::remove_file(&rom_file).unwrap();
+    }
+
+    #[test]
+    fn set_input_test()
+    {
+        let mut gameboy = GameBoy::new();
+        let mut input = PlayerInput::new();
+        input.start = true;
+        gameboy.set_input(input);
+        assert!(gameboy.system_data.input.start);
+        assert!(!gameboy.system_data.input.select);
+    }
+}
@@ -0,0 +1,691 @@
+use std::collections::VecDeque;
+use system::SystemData;
+use gpu::gpu::{pixel_shade_map, pixel_color_map, OAM_SEARCH_END};
+use gpu::gpu_registers::ShadeProfile;
+use image::{ImageBuffer, RgbaImage};
+
+//Dots the first, thrown away, tile fetch of every line takes
+static STARTUP_DOTS: u8 = 6;
+static SPRITE_FETCH_DOTS: u8 = 6;
+static LINE_DOTS: u16 = 456;
+
+#[derive(Clone, Copy)]
+pub struct FifoPixel
+{
+    pub color: u8,
+    pub palette: u8,
+    pub behind_background: bool,
+}
+
+impl FifoPixel
+{
+    fn transparent() -> FifoPixel
+    {
+        return FifoPixel
+        {
+            color: 0,
+            palette: 0,
+            behind_background: false,
+        }
+    }
+}
+
+//Background and window tile fetcher, each of the first three steps takes two dots
+pub struct Fetcher
+{
+    pub step: u8,
+    pub dots: u8,
+    pub tile_x: u8,
+    pub tile_number: u8,
+    pub data_low: u8,
+    pub data_high: u8,
+    pub window: bool,
+}
+
+impl Fetcher
+{
+    pub fn new() -> Fetcher
+    {
+        return Fetcher
+        {
+            step: 0,
+            dots: 0,
+            tile_x: 0,
+            tile_number: 0,
+            data_low: 0,
+            data_high: 0,
+            window: false,
+        }
+    }
+}
+
+//A sprite picked during OAM search, fetched when the pixel output reaches its X
+pub struct LineSprite
+{
+    pub y_position: u8,
+    pub x_position: u8,
+    pub tile_number: u8,
+    pub flags: u8,
+    pub fetched: bool,
+}
+
+//Dot by dot renderer that reads the video registers as it goes, so mid-line writes show up
+pub struct PixelFifo
+{
+    pub background_fifo: VecDeque<FifoPixel>,
+    pub sprite_fifo: VecDeque<FifoPixel>,
+    pub fetcher: Fetcher,
+    pub line_sprites: Vec<LineSprite>,
+    pub sprite_fetch: Option<usize>,
+    pub sprite_fetch_dots: u8,
+    pub startup_dots: u8,
+    pub discard: u8,
+    pub line_x: u8,
+    pub line_done: bool,
+    pub window_line: u8,
+    pub window_y_triggered: bool,
+    pub window_used: bool,
+    //The line ended with the window at WX 166, so the next line starts in the window
+    pub window_carry: bool,
+    pub shades: Vec<u8>,
+}
+
+impl PixelFifo
+{
+    pub fn new() -> PixelFifo
+    {
+        return PixelFifo
+        {
+            background_fifo: VecDeque::with_capacity(16),
+            sprite_fifo: VecDeque::with_capacity(8),
+            fetcher: Fetcher::new(),
+            line_sprites: Vec::with_capacity(10),
+            sprite_fetch: None,
+            sprite_fetch_dots: 0,
+            startup_dots: 0,
+            discard: 0,
+            line_x: 0,
+            line_done: true,
+            window_line: 0,
+            window_y_triggered: false,
+            window_used: false,
+            window_carry: false,
+            shades: vec![0; 160 * 144],
+        }
+    }
+
+    //Clears the frame when the LCD is switched off, the next frame starts over from line 0
+    pub fn blank(&mut self)
+    {
+        for shade in self.shades.iter_mut()
+        {
+            *shade = 0;
+        }
+        self.line_done = true;
+        self.window_line = 0;
+        self.window_y_triggered = false;
+        self.window_carry = false;
+    }
+
+    //Runs the given number of dots starting at line_cycles into line ly
+    pub fn run(&mut self, system_data: &SystemData, ly_value: u8, line_cycles: u16, cycles: u8)
+    {
+        let mut ly_value = ly_value;
+        let mut line_cycles = line_cycles;
+        for _ in 0..cycles
+        {
+            self.dot(system_data, ly_value, line_cycles);
+            line_cycles += 1;
+            if line_cycles == LINE_DOTS
+            {
+                line_cycles = 0;
+                ly_value = (ly_value + 1) % 154;
+            }
+        }
+    }
+
+    pub fn dot(&mut self, system_data: &SystemData, ly_value: u8, line_cycles: u16)
+    {
+        let mem_map = &system_data.mmu.mem_map;
+        if (mem_map[0xFF40] & 0x80) == 0 || ly_value >= 144
+        {
+            if ly_value == 144 && line_cycles == 0
+            {
+                self.window_line = 0;
+                self.window_y_triggered = false;
+                self.window_carry = false;
+            }
+            return;
+        }
+        if line_cycles == 0
+        {
+            //Mode 3 is pending until start_line, not finished from the line before
+            self.line_done = false;
+            if ly_value == mem_map[0xFF4A]
+            {
+                self.window_y_triggered = true;
+            }
+        }
+        if line_cycles == OAM_SEARCH_END
+        {
+            self.start_line(system_data, ly_value);
+        }
+        if line_cycles < OAM_SEARCH_END || self.line_done
+        {
+            return;
+        }
+        if line_cycles == LINE_DOTS - 1
+        {
+            //Never let a line run into the next one
+            self.finish_line(system_data);
+            return;
+        }
+        self.mode_3_dot(system_data, ly_value);
+    }
+
+    fn start_line(&mut self, system_data: &SystemData, ly_value: u8)
+    {
+        let mem_map = &system_data.mmu.mem_map;
+        self.background_fifo.clear();
+        self.sprite_fifo.clear();
+        self.fetcher = Fetcher::new();
+        self.sprite_fetch = None;
+        self.startup_dots = STARTUP_DOTS;
+        self.discard = mem_map[0xFF43] & 0x07;
+        self.line_x = 0;
+        self.line_done = false;
+        self.window_used = false;
+        if self.window_carry && (mem_map[0xFF40] & 0x20) == 0x20
+        {
+            self.fetcher.window = true;
+            self.window_used = true;
+            self.discard = 0;
+        }
+        self.window_carry = false;
+
+        //OAM search, first ten sprites in OAM order that cover this line
+        let height = if (mem_map[0xFF40] & 0x04) == 0x04 { 16 } else { 8 };
+        self.line_sprites.clear();
+        for i in 0..40
+        {
+            let y_position = mem_map[0xFE00 + (i * 4)];
+            let top = y_position as i16 - 16;
+            if (ly_value as i16) >= top && (ly_value as i16) < top + height
+            {
+                self.line_sprites.push(LineSprite
+                {
+                    y_position: y_position,
+                    x_position: mem_map[0xFE01 + (i * 4)],
+                    tile_number: mem_map[0xFE02 + (i * 4)],
+                    flags: mem_map[0xFE03 + (i * 4)],
+                    fetched: false,
+                });
+                if self.line_sprites.len() == 10
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn finish_line(&mut self, system_data: &SystemData)
+    {
+        self.line_done = true;
+        if self.window_used
+        {
+            self.window_line = self.window_line.wrapping_add(1);
+        }
+        self.window_carry = self.window_used && system_data.mmu.mem_map[0xFF4B] == 166;
+    }
+
+    fn mode_3_dot(&mut self, system_data: &SystemData, ly_value: u8)
+    {
+        if self.startup_dots > 0
+        {
+            self.startup_dots -= 1;
+            return;
+        }
+        let lcdc = system_data.mmu.mem_map[0xFF40];
+
+        //Window takes over the fetcher once the output reaches WX - 7
+        let window_x = system_data.mmu.mem_map[0xFF4B];
+        if !self.fetcher.window && (lcdc & 0x20) == 0x20 && self.window_y_triggered && window_x <= 166 && self.line_x as u16 + 7 >= window_x as u16
+        {
+            self.fetcher = Fetcher::new();
+            self.fetcher.window = true;
+            self.background_fifo.clear();
+            self.window_used = true;
+            //Fine scroll only applies to the background, WX below 7 pushes the window off the left edge instead
+            self.discard = if self.line_x == 0 && window_x < 7 { 7 - window_x } else { 0 };
+        }
+
+        if self.sprite_fetch.is_none() && (lcdc & 0x02) == 0x02 && self.discard == 0
+        {
+            self.sprite_fetch = self.next_sprite();
+            self.sprite_fetch_dots = 0;
+        }
+
+        self.fetcher_dot(system_data, ly_value);
+
+        if let Some(index) = self.sprite_fetch
+        {
+            //Sprite fetch waits for the background fetcher to have a tile ready
+            if self.fetcher.step == 3 || !self.background_fifo.is_empty()
+            {
+                self.sprite_fetch_dots += 1;
+                if self.sprite_fetch_dots == SPRITE_FETCH_DOTS
+                {
+                    self.merge_sprite(system_data, ly_value, index);
+                    self.sprite_fetch = None;
+                }
+            }
+            return;
+        }
+
+        self.shift_out(system_data, ly_value);
+    }
+
+    //Several sprites can be due at once, at the left edge or when they overlap. The lowest X is
+    //fetched first so it keeps the shared pixels, OAM order breaks ties like on the DMG.
+    fn next_sprite(&mut self) -> Option<usize>
+    {
+        let mut next: Option<usize> = None;
+        for i in 0..self.line_sprites.len()
+        {
+            let sprite = &self.line_sprites[i];
+            if sprite.fetched || sprite.x_position as u16 > self.line_x as u16 + 8
+            {
+                continue;
+            }
+            if next.map_or(true, |n| sprite.x_position < self.line_sprites[n].x_position)
+            {
+                next = Some(i);
+            }
+        }
+        if let Some(i) = next
+        {
+            self.line_sprites[i].fetched = true;
+        }
+        return next;
+    }
+
+    fn fetcher_dot(&mut self, system_data: &SystemData, ly_value: u8)
+    {
+        if self.fetcher.step == 3
+        {
+            if self.background_fifo.is_empty()
+            {
+                self.push_tile(system_data);
+            }
+            return;
+        }
+        self.fetcher.dots += 1;
+        if self.fetcher.dots < 2
+        {
+            return;
+        }
+        self.fetcher.dots = 0;
+        let mem_map = &system_data.mmu.mem_map;
+        let lcdc = mem_map[0xFF40];
+        match self.fetcher.step
+        {
+            0 =>
+            {
+                let (map_base, tile_x, tile_y) = if self.fetcher.window
+                {
+                    let map_base = if (lcdc & 0x40) == 0x40 { 0x9C00 } else { 0x9800 };
+                    (map_base, self.fetcher.tile_x as usize, self.window_line as usize / 8)
+                }
+                else
+                {
+                    let map_base = if (lcdc & 0x08) == 0x08 { 0x9C00 } else { 0x9800 };
+                    let tile_x = ((mem_map[0xFF43] / 8) as usize + self.fetcher.tile_x as usize) & 0x1F;
+                    (map_base, tile_x, ly_value.wrapping_add(mem_map[0xFF42]) as usize / 8)
+                };
+                self.fetcher.tile_number = mem_map[map_base + (tile_y * 32) + tile_x];
+            },
+            _ =>
+            {
+                let row = if self.fetcher.window { self.window_line & 0x07 } else { ly_value.wrapping_add(mem_map[0xFF42]) & 0x07 };
+                let address = if (lcdc & 0x10) == 0x10
+                {
+                    0x8000 + (self.fetcher.tile_number as usize * 16)
+                }
+                else
+                {
+                    (0x9000 + (self.fetcher.tile_number as i8 as i32 * 16)) as usize
+                };
+                let data = mem_map[address + (row as usize * 2) + (self.fetcher.step as usize - 1)];
+                if self.fetcher.step == 1
+                {
+                    self.fetcher.data_low = data;
+                }
+                else
+                {
+                    self.fetcher.data_high = data;
+                }
+            },
+        }
+        self.fetcher.step += 1;
+    }
+
+    fn push_tile(&mut self, system_data: &SystemData)
+    {
+        let background_enable = (system_data.mmu.mem_map[0xFF40] & 0x01) == 0x01;
+        for bit in (0..8).rev()
+        {
+            let mut color = (((self.fetcher.data_high >> bit) & 0x01) << 1) | ((self.fetcher.data_low >> bit) & 0x01);
+            if !background_enable
+            {
+                color = 0;
+            }
+            self.background_fifo.push_back(FifoPixel
+            {
+                color: color,
+                palette: 0,
+                behind_background: false,
+            });
+        }
+        self.fetcher.tile_x = self.fetcher.tile_x.wrapping_add(1);
+        self.fetcher.step = 0;
+    }
+
+    //Sprite pixels only fill slots that are still transparent, so earlier fetched sprites keep priority
+    fn merge_sprite(&mut self, system_data: &SystemData, ly_value: u8, index: usize)
+    {
+        let mem_map = &system_data.mmu.mem_map;
+        let large_sprites = (mem_map[0xFF40] & 0x04) == 0x04;
+        let sprite = &self.line_sprites[index];
+        //LCDC.2 can change after OAM search picked the sprite, so only the row bits the current
+        //size uses are kept like the hardware does instead of trusting the selection height
+        let height: i16 = if large_sprites { 16 } else { 8 };
+        let mut row = (ly_value as i16 - (sprite.y_position as i16 - 16)) & (height - 1);
+        if (sprite.flags & 0x40) == 0x40
+        {
+            row = height - 1 - row;
+        }
+        let tile_number = if large_sprites { sprite.tile_number & 0xFE } else { sprite.tile_number };
+        let address = 0x8000 + (tile_number as usize * 16) + (row as usize * 2);
+        let data_low = mem_map[address];
+        let data_high = mem_map[address + 1];
+
+        while self.sprite_fifo.len() < 8
+        {
+            self.sprite_fifo.push_back(FifoPixel::transparent());
+        }
+        //Sprites hanging off the left edge lose their first columns
+        let clipped = self.line_x + 8 - sprite.x_position;
+        for column in clipped..8
+        {
+            let bit = if (sprite.flags & 0x20) == 0x20 { column } else { 7 - column };
+            let color = (((data_high >> bit) & 0x01) << 1) | ((data_low >> bit) & 0x01);
+            let slot = (column - clipped) as usize;
+            if color != 0 && self.sprite_fifo[slot].color == 0
+            {
+                self.sprite_fifo[slot] = FifoPixel
+                {
+                    color: color,
+                    palette: (sprite.flags & 0x10) >> 4,
+                    behind_background: (sprite.flags & 0x80) == 0x80,
+                };
+            }
+        }
+    }
+
+    fn shift_out(&mut self, system_data: &SystemData, ly_value: u8)
+    {
+        let background_pixel = match self.background_fifo.pop_front()
+        {
+            Some(pixel) => pixel,
+            None => return,
+        };
+        if self.discard > 0
+        {
+            self.discard -= 1;
+            return;
+        }
+        let mem_map = &system_data.mmu.mem_map;
+        let mut shade = pixel_shade_map(background_pixel.color, mem_map[0xFF47]);
+        if let Some(sprite_pixel) = self.sprite_fifo.pop_front()
+        {
+            let sprites_enabled = (mem_map[0xFF40] & 0x02) == 0x02;
+            if sprites_enabled && sprite_pixel.color != 0 && !(sprite_pixel.behind_background && background_pixel.color != 0)
+            {
+                let palette = if sprite_pixel.palette == 1 { mem_map[0xFF49] } else { mem_map[0xFF48] };
+                shade = pixel_shade_map(sprite_pixel.color, palette);
+            }
+        }
+        self.shades[(ly_value as usize * 160) + self.line_x as usize] = shade;
+        self.line_x += 1;
+        if self.line_x == 160
+        {
+            self.finish_line(system_data);
+        }
+    }
+
+    pub fn frame_image(&self, shade_profile: &ShadeProfile) -> RgbaImage
+    {
+        let mut image_buffer = ImageBuffer::new(160, 144);
+        for row_y in 0..144
+        {
+            for row_x in 0..160
+            {
+                let pixel = pixel_color_map(self.shades[(row_y * 160) + row_x], shade_profile);
+                image_buffer.put_pixel(row_x as u32, row_y as u32, pixel);
+            }
+        }
+        return image_buffer;
+    }
+}
+
+#[cfg(test)]
+mod fifo_tests
+{
+    use gpu::fifo::*;
+    use system::get_system_data;
+
+    //Returns how many dots mode 3 took on the given line
+    fn mode_3_length(fifo: &mut PixelFifo, system_data: &SystemData, ly_value: u8) -> u16
+    {
+        for line_cycles in 0..LINE_DOTS
+        {
+            fifo.dot(system_data, ly_value, line_cycles);
+            if line_cycles >= OAM_SEARCH_END && fifo.line_done
+            {
+                return line_cycles + 1 - OAM_SEARCH_END;
+            }
+        }
+        return LINE_DOTS;
+    }
+
+    #[test]
+    fn mode_3_length_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        system_data.mmu.mem_map[0xFF40] = 0x91;
+        let mut fifo = PixelFifo::new();
+        assert_eq!(mode_3_length(&mut fifo, &system_data, 0), 172);
+
+        system_data.mmu.mem_map[0xFF43] = 0x03;
+        assert_eq!(mode_3_length(&mut fifo, &system_data, 1), 175);
+
+        //A sprite stalls the output while it is fetched
+        system_data.mmu.mem_map[0xFF43] = 0x00;
+        system_data.mmu.mem_map[0xFF40] = 0x93;
+        system_data.mmu.mem_map[0xFE00] = 16 + 2;
+        system_data.mmu.mem_map[0xFE01] = 8 + 40;
+        let length = mode_3_length(&mut fifo, &system_data, 2);
+        assert!(length >= 172 + 6 && length <= 172 + 11);
+    }
+
+    #[test]
+    fn background_and_sprite_pixels_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        system_data.mmu.mem_map[0xFF40] = 0x93;
+        system_data.mmu.mem_map[0xFF47] = 0xE4;
+        system_data.mmu.mem_map[0xFF48] = 0xE4;
+        //Tile 1 is solid color 1, tile 2 is solid color 3
+        for i in 0..16
+        {
+            system_data.mmu.mem_map[0x8010 + i] = if i % 2 == 0 { 0xFF } else { 0x00 };
+            system_data.mmu.mem_map[0x8020 + i] = 0xFF;
+        }
+        system_data.mmu.mem_map[0x9801] = 1;
+        //Sprite using tile 2 at screen X 4, half over tile 0 and half over tile 1, drawn behind the background
+        system_data.mmu.mem_map[0xFE00] = 16;
+        system_data.mmu.mem_map[0xFE01] = 8 + 4;
+        system_data.mmu.mem_map[0xFE02] = 2;
+        system_data.mmu.mem_map[0xFE03] = 0x80;
+
+        let mut fifo = PixelFifo::new();
+        fifo.run(&system_data, 0, 0, 255);
+        fifo.run(&system_data, 0, 255, 201);
+        let line: Vec<u8> = fifo.shades[0..20].to_vec();
+        assert_eq!(line, vec![0, 0, 0, 0, 3, 3, 3, 3, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn mid_line_palette_write_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        system_data.mmu.mem_map[0xFF40] = 0x91;
+        system_data.mmu.mem_map[0xFF47] = 0x00;
+        let mut fifo = PixelFifo::new();
+        //First pixel comes out 12 dots into mode 3
+        for line_cycles in 0..(OAM_SEARCH_END + 12 + 80)
+        {
+            fifo.dot(&system_data, 0, line_cycles);
+        }
+        system_data.mmu.mem_map[0xFF47] = 0x03;
+        for line_cycles in (OAM_SEARCH_END + 12 + 80)..LINE_DOTS
+        {
+            fifo.dot(&system_data, 0, line_cycles);
+        }
+        assert_eq!(fifo.shades[79], 0);
+        assert_eq!(fifo.shades[80], 3);
+    }
+
+    #[test]
+    fn overlapping_sprites_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        system_data.mmu.mem_map[0xFF40] = 0x93;
+        system_data.mmu.mem_map[0xFF47] = 0xE4;
+        system_data.mmu.mem_map[0xFF48] = 0xE4;
+        //Tile 1 is solid color 1, tile 2 is solid color 3
+        for i in 0..16
+        {
+            system_data.mmu.mem_map[0x8010 + i] = if i % 2 == 0 { 0xFF } else { 0x00 };
+            system_data.mmu.mem_map[0x8020 + i] = 0xFF;
+        }
+        //Both sprites are due at the left edge, the later OAM entry sits further left and wins
+        system_data.mmu.mem_map[0xFE00] = 16;
+        system_data.mmu.mem_map[0xFE01] = 8;
+        system_data.mmu.mem_map[0xFE02] = 2;
+        system_data.mmu.mem_map[0xFE04] = 16;
+        system_data.mmu.mem_map[0xFE05] = 6;
+        system_data.mmu.mem_map[0xFE06] = 1;
+        //Same X, the first OAM entry wins
+        system_data.mmu.mem_map[0xFE08] = 16;
+        system_data.mmu.mem_map[0xFE09] = 8 + 40;
+        system_data.mmu.mem_map[0xFE0A] = 2;
+        system_data.mmu.mem_map[0xFE0C] = 16;
+        system_data.mmu.mem_map[0xFE0D] = 8 + 40;
+        system_data.mmu.mem_map[0xFE0E] = 1;
+
+        let mut fifo = PixelFifo::new();
+        fifo.run(&system_data, 0, 0, 228);
+        fifo.run(&system_data, 0, 228, 228);
+        assert_eq!(fifo.shades[0..8].to_vec(), vec![1, 1, 1, 1, 1, 1, 3, 3]);
+        assert_eq!(fifo.shades[40..48].to_vec(), vec![3; 8]);
+    }
+
+    #[test]
+    fn sprite_size_change_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        //A Y flipped 8x16 sprite covering line 12 with its lower half
+        system_data.mmu.mem_map[0xFF40] = 0x97;
+        system_data.mmu.mem_map[0xFE00] = 16;
+        system_data.mmu.mem_map[0xFE01] = 8 + 40;
+        system_data.mmu.mem_map[0xFE03] = 0x40;
+        let mut fifo = PixelFifo::new();
+        for line_cycles in 0..(OAM_SEARCH_END + 1)
+        {
+            fifo.dot(&system_data, 12, line_cycles);
+        }
+        assert_eq!(fifo.line_sprites.len(), 1);
+        //Switching to 8x8 before the sprite is fetched must not underflow the row
+        system_data.mmu.mem_map[0xFF40] = 0x93;
+        for line_cycles in (OAM_SEARCH_END + 1)..LINE_DOTS
+        {
+            fifo.dot(&system_data, 12, line_cycles);
+        }
+        assert!(fifo.line_done);
+        assert!(fifo.line_sprites[0].fetched);
+    }
+
+    #[test]
+    fn window_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        //Window uses the 0x9C00 map filled with the solid tile 1
+        system_data.mmu.mem_map[0xFF40] = 0xF1;
+        system_data.mmu.mem_map[0xFF47] = 0xE4;
+        system_data.mmu.mem_map[0xFF4A] = 1;
+        system_data.mmu.mem_map[0xFF4B] = 7 + 100;
+        for i in 0..16
+        {
+            system_data.mmu.mem_map[0x8010 + i] = 0xFF;
+        }
+        for i in 0..0x400
+        {
+            system_data.mmu.mem_map[0x9C00 + i] = 1;
+        }
+        let mut fifo = PixelFifo::new();
+        for ly_value in 0..3
+        {
+            fifo.run(&system_data, ly_value, 0, 228);
+            fifo.run(&system_data, ly_value, 228, 228);
+        }
+        assert_eq!(fifo.shades[(0 * 160) + 120], 0);
+        assert_eq!(fifo.shades[(1 * 160) + 99], 0);
+        assert_eq!(fifo.shades[(1 * 160) + 100], 3);
+        assert_eq!(fifo.shades[(2 * 160) + 159], 3);
+        assert_eq!(fifo.window_line, 2);
+    }
+
+    #[test]
+    fn window_wx_166_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        system_data.mmu.mem_map[0xFF40] = 0xF1;
+        system_data.mmu.mem_map[0xFF47] = 0xE4;
+        system_data.mmu.mem_map[0xFF4B] = 166;
+        for i in 0..16
+        {
+            system_data.mmu.mem_map[0x8010 + i] = 0xFF;
+        }
+        for i in 0..0x400
+        {
+            system_data.mmu.mem_map[0x9C00 + i] = 1;
+        }
+        let mut fifo = PixelFifo::new();
+        fifo.run(&system_data, 0, 0, 228);
+        fifo.run(&system_data, 0, 228, 228);
+        assert_eq!(fifo.shades[158], 0);
+        assert_eq!(fifo.shades[159], 3);
+
+        //WX 167 would hide the window, but the line after WX 166 is covered by it
+        system_data.mmu.mem_map[0xFF4B] = 167;
+        for ly_value in 1..3
+        {
+            fifo.run(&system_data, ly_value, 0, 228);
+            fifo.run(&system_data, ly_value, 228, 228);
+        }
+        assert_eq!(fifo.shades[(1 * 160)..(2 * 160)].to_vec(), vec![3; 160]);
+        assert_eq!(fifo.shades[(2 * 160) + 80], 0);
+        assert_eq!(fifo.window_line, 2);
+    }
+}
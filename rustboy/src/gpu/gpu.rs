@@ -1,5 +1,5 @@
 use system::*;
-use gpu::gpu_registers::{GPU_Registers, ShadeProfile, LCD_Position};
+use gpu::gpu_registers::{GPU_Registers, ShadeProfile};
 use image::ImageBuffer;
 use image::{RgbaImage, Rgba};
 
@@ -160,10 +160,98 @@ pub fn update_gpu(system_data_original: &mut SystemData, registers_original: &mu
     let mut system_data = system_data_original;
     let mut registers = registers_original;
     let mut gpu_registers = gpu_registers_original;
+    if (system_data.mmu.mem_map[0xFF40] & 0x80) == 0
+    {
+        lcd_off_update(&mut system_data, &mut gpu_registers);
+        return;
+    }
+    if !gpu_registers.lcd_enabled
+    {
+        //Line 0 starts here rather than on an LY change, so it is latched now
+        gpu_registers.lcd_enabled = true;
+        gpu_registers.lcd_position.ly_register.reset(&mut system_data);
+        latch_line_registers(&system_data, &mut gpu_registers, 0);
+    }
+    if let Some(ref mut pixel_fifo) = gpu_registers.pixel_fifo
+    {
+        let ly_register = &gpu_registers.lcd_position.ly_register;
+        pixel_fifo.run(&system_data, ly_register.value, ly_register.sub_cycle_count, system_data.cycles);
+    }
     LCD_Y_Coordinate_Update(&mut system_data, &mut gpu_registers);
     gpu_registers.lcdc_register.update_lcdc_register(&system_data);
+    gpu_registers.lcd_position.update(&mut system_data);
+    let ly_value = gpu_registers.lcd_position.ly_register.value;
+    let line_cycles = gpu_registers.lcd_position.ly_register.sub_cycle_count;
+    let mode = match gpu_registers.pixel_fifo
+    {
+        //Mode 3 runs until the FIFO has shifted out the whole line
+        Some(ref pixel_fifo) if ly_value < 144 && line_cycles >= OAM_SEARCH_END => if pixel_fifo.line_done { 0 } else { 3 },
+        _ => lcd_mode(ly_value, line_cycles),
+    };
+    system_data.mmu.mem_map[0xFF41] = (system_data.mmu.mem_map[0xFF41] & 0xFC) | mode;
     gpu_registers.lcdc_status.update_lcdc_status(&system_data);
-    gpu_registers.lcd_position.update(&mut system_data, gpu_registers.lcdc_status.lyc_ly_coincidence_interrupt);
+    stat_interrupt_update(&mut system_data, &mut gpu_registers);
+}
+
+//LY is held at 0 in mode 0 with no STAT sources active while the LCD is off. The frontend
+//still gets a blank frame every 70224 cycles, without the v-blank interrupt.
+fn lcd_off_update(system_data: &mut SystemData, gpu_registers: &mut GPU_Registers)
+{
+    if gpu_registers.lcd_enabled
+    {
+        gpu_registers.lcd_enabled = false;
+        gpu_registers.lcd_position.ly_register.reset(system_data);
+        gpu_registers.v_blank = false;
+        gpu_registers.stat_interrupt_line = false;
+        for line in 0..144
+        {
+            gpu_registers.lcdc_buffer[line] = system_data.mmu.mem_map[0xFF40];
+        }
+        if let Some(ref mut pixel_fifo) = gpu_registers.pixel_fifo
+        {
+            pixel_fifo.blank();
+        }
+    }
+    system_data.mmu.mem_map[0xFF41] &= 0xFC;
+    gpu_registers.lcdc_status.update_lcdc_status(system_data);
+    if gpu_registers.lcd_position.ly_register.add_cycles(system_data)
+    {
+        gpu_registers.v_blank_draw_flag = true;
+    }
+}
+
+//Cycles into a visible line at which OAM search and pixel transfer end
+pub static OAM_SEARCH_END: u16 = 80;
+pub static PIXEL_TRANSFER_END: u16 = 252;
+
+//Mode 2 OAM search, mode 3 pixel transfer and mode 0 h-blank on lines 0-143, mode 1 v-blank after
+pub fn lcd_mode(ly_value: u8, line_cycles: u16) -> u8
+{
+    if ly_value >= 144
+    {
+        return 1;
+    }
+    if line_cycles < OAM_SEARCH_END
+    {
+        return 2;
+    }
+    if line_cycles < PIXEL_TRANSFER_END
+    {
+        return 3;
+    }
+    return 0;
+}
+
+//Requests the STAT interrupt when the first enabled source becomes active, sources that
+//stay active or overlap do not request it again
+fn stat_interrupt_update(system_data: &mut SystemData, gpu_registers: &mut GPU_Registers)
+{
+    let interrupt_line = gpu_registers.lcdc_status.interrupt_line();
+    if interrupt_line && !gpu_registers.stat_interrupt_line
+    {
+        system_data.mmu.mem_map[0xFF0F] |= 0x02;
+    }
+    gpu_registers.stat_interrupt_line = interrupt_line;
 }
 
 pub fn LCD_Y_Coordinate_Update(system_data_original: &mut SystemData, gpu_registers: &mut GPU_Registers)
@@ -184,10 +272,7 @@ pub fn LCD_Y_Coordinate_Update(system_data_original: &mut SystemData, gpu_regist
         }
         else if ly_value < 144
         {
-            gpu_registers.lcd_position.scroll_x_buffer[ly_value as usize] = gpu_registers.lcd_position.scroll_x;
-            gpu_registers.lcd_position.scroll_y_buffer[ly_value as usize] = gpu_registers.lcd_position.scroll_y;
-            gpu_registers.lcd_position.window_x_buffer[ly_value as usize] = gpu_registers.lcd_position.window_x;
-            gpu_registers.lcd_position.window_y_buffer[ly_value as usize] = gpu_registers.lcd_position.window_y;
+            latch_line_registers(&system_data, gpu_registers, ly_value as usize);
         }
         if reset_flag
         {
@@ -197,24 +282,60 @@ pub fn LCD_Y_Coordinate_Update(system_data_original: &mut SystemData, gpu_regist
     }
 }
 
-pub fn create_background_img(background_tile_map: &TileMap, window_tile_map: &TileMap, gpu_registers: &GPU_Registers, system_data: &SystemData, oam_table: &OAM_Table, oam_tile_map: &TileMap) -> RgbaImage
+//Copies the registers a line is drawn with when it starts
+fn latch_line_registers(system_data: &SystemData, gpu_registers: &mut GPU_Registers, line: usize)
 {
-    //Technically switchable by the row, will implement later
-    let palette_data = system_data.mmu.mem_map[0xFF47];
-    let object_palette_0 = system_data.mmu.mem_map[0xFF48];
-    let object_palette_1 = system_data.mmu.mem_map[0xFF49];
-    let mut image_buffer = ImageBuffer::new(160, 144);
-    let background_buffer = build_bitmap(background_tile_map, gpu_registers.lcdc_register.tile_data, palette_data, gpu_registers.lcdc_register.display_enable);
-    let mut scrolled_buffer = scroll_background_bitmap(background_buffer, &gpu_registers.lcd_position);
-    if gpu_registers.lcdc_register.window_enable
+    let mem_map = &system_data.mmu.mem_map;
+    gpu_registers.lcd_position.scroll_x_buffer[line] = mem_map[0xFF43];
+    gpu_registers.lcd_position.scroll_y_buffer[line] = mem_map[0xFF42];
+    gpu_registers.lcd_position.window_x_buffer[line] = mem_map[0xFF4B];
+    gpu_registers.lcd_position.window_y_buffer[line] = mem_map[0xFF4A];
+    gpu_registers.lcdc_buffer[line] = mem_map[0xFF40];
+    gpu_registers.background_palette_buffer[line] = mem_map[0xFF47];
+    gpu_registers.object_palette_0_buffer[line] = mem_map[0xFF48];
+    gpu_registers.object_palette_1_buffer[line] = mem_map[0xFF49];
+}
+
+//Background and window bitmaps of raw colour numbers, one per tile map and tile data
+//combination, built the first time a line asks for them
+pub struct TileBitmaps
+{
+    pub bitmaps: Vec<Option<Vec<u8>>>,
+}
+
+impl TileBitmaps
+{
+    pub fn new() -> TileBitmaps
     {
-        let window_buffer = build_bitmap(window_tile_map, gpu_registers.lcdc_register.tile_data, palette_data, gpu_registers.lcdc_register.window_enable);
-        scrolled_buffer = place_window(scrolled_buffer, window_buffer, &gpu_registers.lcd_position)
+        return TileBitmaps
+        {
+            bitmaps: vec![None; 4],
+        }
     }
-    if gpu_registers.lcdc_register.sprite_enable
+
+    pub fn get(&mut self, system_data: &mut SystemData, map_select: bool, data_select: bool) -> &Vec<u8>
     {
-        scrolled_buffer = apply_oam_table_to_bitmap(&oam_table, scrolled_buffer, object_palette_0, object_palette_1, gpu_registers.lcdc_register.sprite_size, &oam_tile_map);
+        let index = ((map_select as usize) << 1) | data_select as usize;
+        if self.bitmaps[index].is_none()
+        {
+            let mut tile_map = TileMap::new();
+            tile_map.populate_tile_map(system_data, data_select, map_select);
+            self.bitmaps[index] = Some(build_bitmap(&tile_map, data_select));
+        }
+        return self.bitmaps[index].as_ref().unwrap();
     }
+}
+
+//Each line is drawn with the LCDC, palettes and scroll values latched when it started, so the
+//tile map and tile data bases can switch per line. The tiles themselves are read from VRAM
+//once when the frame is composed.
+pub fn create_background_img(gpu_registers: &GPU_Registers, system_data: &mut SystemData, oam_table: &OAM_Table, oam_tile_map: &TileMap) -> RgbaImage
+{
+    let mut image_buffer = ImageBuffer::new(160, 144);
+    let mut tile_bitmaps = TileBitmaps::new();
+    let mut scrolled_buffer = scroll_background_bitmap(&mut tile_bitmaps, system_data, gpu_registers);
+    scrolled_buffer = place_window(scrolled_buffer, &mut tile_bitmaps, system_data, gpu_registers);
+    scrolled_buffer = apply_oam_table_to_bitmap(&oam_table, scrolled_buffer, gpu_registers, &oam_tile_map);
     for row_y in 0..144
     {
         for row_x in 0..160
@@ -227,11 +348,19 @@ pub fn create_background_img(background_tile_map: &TileMap, window_tile_map: &Ti
    return image_buffer;
 }
 
-fn scroll_background_bitmap(buffer: Vec<u8>, scroll: &LCD_Position) -> Vec<u8>
+fn scroll_background_bitmap(tile_bitmaps: &mut TileBitmaps, system_data: &mut SystemData, gpu_registers: &GPU_Registers) -> Vec<u8>
 {
+    let scroll = &gpu_registers.lcd_position;
     let mut bitmap = vec![0; 0x5A00];
     for row_y in 0..144
     {
+        //Lines with the LCD or background turned off keep colour 0 and are drawn white
+        let lcdc = gpu_registers.lcdc_buffer[row_y];
+        if lcdc & 0x81 != 0x81
+        {
+            continue;
+        }
+        let buffer = tile_bitmaps.get(system_data, (lcdc & 0x08) != 0, (lcdc & 0x10) != 0);
         for row_x in 0..160
         {
            let row_x_scrolled = (row_x + scroll.scroll_x_buffer[row_y] as usize) % 256;
@@ -243,30 +372,51 @@ fn scroll_background_bitmap(buffer: Vec<u8>, scroll: &LCD_Position) -> Vec<u8>
     return bitmap;
 }
 
-fn place_window(scrolled_bitmap: Vec<u8>, window_bitmap: Vec<u8>, scroll: &LCD_Position) -> Vec<u8>
+//The window keeps its own line counter that only advances on lines where it was drawn,
+//so turning it off part way down the frame resumes from the same window row later
+fn place_window(scrolled_bitmap: Vec<u8>, tile_bitmaps: &mut TileBitmaps, system_data: &mut SystemData, gpu_registers: &GPU_Registers) -> Vec<u8>
 {
+    let scroll = &gpu_registers.lcd_position;
     let mut bitmap = scrolled_bitmap;
+    let mut window_line: usize = 0;
+    let mut window_y_triggered = false;
+    let mut carry_over = false;
     for row_y in 0..144
     {
-        for row_x in 0..160
+        //Once LY has matched WY the window may appear on any later line of the frame
+        if scroll.window_y_buffer[row_y] as usize == row_y
         {
-            let scrolled_y = (row_y + scroll.window_y_buffer[row_y] as usize);
-            let mut possible_negative = (row_x + scroll.window_x_buffer[row_y] as usize - 7);
-            if possible_negative < 0
-            {
-                possible_negative = 0xFF;
-            }
-            let scrolled_x = possible_negative as usize;
-            if scrolled_y < 144 && scrolled_x < 160
-            {
-                bitmap[(scrolled_y * 160) + scrolled_x] = window_bitmap[(row_y * 256) + row_x];
-            }
+            window_y_triggered = true;
+        }
+        let lcdc = gpu_registers.lcdc_buffer[row_y];
+        let mut window_x = scroll.window_x_buffer[row_y] as usize;
+        let carried = carry_over;
+        carry_over = false;
+        if !window_y_triggered || (lcdc & 0xA1) != 0xA1 || (window_x > 166 && !carried)
+        {
+            continue;
+        }
+        //WX 166 only shows the last pixel, but the window fetcher is still running when the
+        //next line starts, so the DMG covers that whole line with the window
+        carry_over = window_x == 166;
+        if carried
+        {
+            window_x = 7;
+        }
+        //WX below 7 starts at the left edge with the first columns cut off
+        let start_x = if window_x < 7 { 0 } else { window_x - 7 };
+        let window_bitmap = tile_bitmaps.get(system_data, (lcdc & 0x40) != 0, (lcdc & 0x10) != 0);
+        for row_x in start_x..160
+        {
+            let window_column = row_x + 7 - window_x;
+            bitmap[(row_y * 160) + row_x] = window_bitmap[((window_line % 256) * 256) + window_column];
         }
+        window_line += 1;
     }
     return bitmap;
 }
 
-fn build_bitmap(background_tile_map: &TileMap, tile_data_select: bool, palette_data:u8, enable: bool) -> Vec<u8>
+fn build_bitmap(background_tile_map: &TileMap, tile_data_select: bool) -> Vec<u8>
 {
 
     let mut buffer = vec![0; 0x10000];
@@ -284,15 +434,8 @@ fn build_bitmap(background_tile_map: &TileMap, tile_data_select: bool, palette_d
                         let tile_temp = tile as i8 as i16;
                         tile = (tile_temp + 0x80) as u8;
                     }
-                    let mut pixel_data = background_tile_map.tiles[tile as usize].data[(pixel_y * 8) + pixel_x];
-                    pixel_data = pixel_shade_map(pixel_data, palette_data);
-                    if enable{
-                        buffer[(256 * ((tile_y * 8) + pixel_y)) + ((tile_x * 8) + pixel_x)] = pixel_data;
-                    }
-                    else 
-                    {
-                        buffer[(256 * ((tile_y * 8) + pixel_y)) + ((tile_x * 8) + pixel_x)] = 0;
-                    }
+                    let pixel_data = background_tile_map.tiles[tile as usize].data[(pixel_y * 8) + pixel_x];
+                    buffer[(256 * ((tile_y * 8) + pixel_y)) + ((tile_x * 8) + pixel_x)] = pixel_data;
                 }
             }
         }
@@ -300,165 +443,114 @@ fn build_bitmap(background_tile_map: &TileMap, tile_data_select: bool, palette_d
     return buffer;
 }
 
-fn apply_oam_table_to_bitmap(oam_table: &OAM_Table, scrolled_bitmap: Vec<u8>, palette_0: u8, palette_1: u8, large_sprites: bool, tile_map: &TileMap) -> Vec<u8>
+//Sprites covering a line, at most ten taken in OAM order the way OAM search picks them.
+//Returned in drawing priority, on the DMG the lower X wins and OAM index breaks ties.
+pub fn line_sprites(oam_table: &OAM_Table, line: u8, large_sprites: bool) -> Vec<usize>
 {
-    let mut bitmap = scrolled_bitmap;
-    let mut tile = TileData::new();
-    let mut tile_big = TileData::new();
+    let height = if large_sprites { 16 } else { 8 };
+    let mut sprites: Vec<usize> = vec![];
     for i in 0..oam_table.table.len()
     {
-        let y_position = oam_table.table[i].y_position as i16 - 16;
-        let x_position = oam_table.table[i].x_position as i16 - 8;
-        let tile_num = oam_table.table[i].tile_number as usize;
-        if !large_sprites
-        {
-            tile = tile_map.tiles[tile_num].clone(); 
-        }
-        else 
+        let top = oam_table.table[i].y_position as i16 - 16;
+        if (line as i16) >= top && (line as i16) < top + height
         {
-            tile = tile_map.tiles[tile_num & 0xFE].clone();
-            tile_big = tile_map.tiles[tile_num | 0x01].clone();
-        }
-
-        let flags = oam_table.table[i].flags;
-        let y_flip = (flags & 0x40) >> 6;
-        let x_flip = (flags & 0x20) >> 5;
-        let mut palette = 0;
-        let behind = (flags & 0x80) >> 7;
-        if ((flags & 0x10) >> 4) == 1
-        {
-            palette = palette_1;
-        }
-        else 
-        {
-            palette = palette_0;    
-        }
-
-        if !large_sprites
-        {
-            if y_flip == 1
+            sprites.push(i);
+            if sprites.len() == 10
             {
-                for row in 0..4
-                {
-                    for index in 0..8
-                    {
-                        let temp = tile.data[(row * 8) + index];
-                        tile.data[(row * 8) + index] = tile.data[((7 - row) * 8) + index];
-                        tile.data[((7 - row) * 8) + index] = temp;
-                    }
-                }
-            }
-
-            if x_flip == 1
-            {
-                for column in 0..4
-                {
-                    for row in 0..8
-                    {
-                        let temp = tile.data[(row * 8) + column];
-                        tile.data[(row * 8) + column] = tile.data[(row * 8) + (7 - column)];
-                        tile.data[(row * 8) + (7 - column)] = temp;
-                    }
-                }
+                break;
             }
         }
-        else 
-        {
-            if y_flip == 1
-            {
-                for row in 0..8
-                {
-                    for index in 0..8
-                    {
-                        let temp = tile.data[(row * 8) + index];
-                        tile.data[(row * 8) + index] = tile_big.data[((7 - row) * 8) + index];
-                        tile_big.data[((7 - row) * 8) + index] = temp;
-                    }
-                }
-            }
+    }
+    //Stable sort, so equal X keeps OAM order
+    sprites.sort_by_key(|&i| oam_table.table[i].x_position);
+    return sprites;
+}
+
+//Colour number of a sprite at a screen position, 0 is transparent
+fn sprite_pixel(sprite: &SpriteAttribute, tile_map: &TileMap, line: u8, row_x: usize, large_sprites: bool) -> u8
+{
+    let column = row_x as i16 - (sprite.x_position as i16 - 8);
+    if column < 0 || column >= 8
+    {
+        return 0;
+    }
+    let height = if large_sprites { 16 } else { 8 };
+    let mut row = line as i16 - (sprite.y_position as i16 - 16);
+    if (sprite.flags & 0x40) == 0x40
+    {
+        row = height - 1 - row;
+    }
+    let mut column = column;
+    if (sprite.flags & 0x20) == 0x20
+    {
+        column = 7 - column;
+    }
+    let mut tile_num = sprite.tile_number as usize;
+    if large_sprites
+    {
+        tile_num = (tile_num & 0xFE) + (row as usize / 8);
+    }
+    return tile_map.tiles[tile_num].data[((row as usize % 8) * 8) + column as usize];
+}
 
-            if x_flip == 1
+//Takes the background and window colour numbers and returns shades with the sprites on top
+fn apply_oam_table_to_bitmap(oam_table: &OAM_Table, scrolled_bitmap: Vec<u8>, gpu_registers: &GPU_Registers, tile_map: &TileMap) -> Vec<u8>
+{
+    let mut bitmap = vec![0; 0x5A00];
+    for row_y in 0..144
+    {
+        let lcdc = gpu_registers.lcdc_buffer[row_y];
+        let large_sprites = (lcdc & 0x04) == 0x04;
+        let sprites = line_sprites(oam_table, row_y as u8, large_sprites);
+        for row_x in 0..160
+        {
+            let position = (row_y * 160) + row_x;
+            let background = scrolled_bitmap[position];
+            if (lcdc & 0x81) == 0x81
             {
-                for column in 0..4
-                {
-                    for row in 0..8
-                    {
-                        let temp = tile.data[(row * 8) + column];
-                        tile.data[(row * 8) + column] = tile.data[(row * 8) + (7 - column)];
-                        tile.data[(row * 8) + (7 - column)] = temp;
-                    }
-                    for row in 0..8
-                    {
-                        let temp = tile_big.data[(row * 8) + column];
-                        tile_big.data[(row * 8) + column] = tile_big.data[(row * 8) + (7 - column)];
-                        tile_big.data[(row * 8) + (7 - column)] = temp;
-                    }
-                }
+                bitmap[position] = pixel_shade_map(background, gpu_registers.background_palette_buffer[row_y]);
             }
-        }
-        
-        if !large_sprites
-        {
-            for row in 0..8
+            //The first opaque sprite decides the pixel, even when it ends up hidden behind the background
+            for &i in sprites.iter()
             {
-                for index in 0..8
+                let sprite = &oam_table.table[i];
+                let pixel = sprite_pixel(sprite, tile_map, row_y as u8, row_x, large_sprites);
+                if pixel == 0
                 {
-                    if (y_position + row) >= 0 && (y_position + row) < 144
-                    {
-                        if (x_position + index) >= 0 && (x_position + index) < 160
-                        {
-                            let tile_position = ((y_position + row) * 160) + (x_position + index);
-                            let pixel = tile.data[(row as usize* 8) + index as usize];
-                            if pixel != 0
-                            {
-                                let shade = pixel_shade_map(pixel, palette);
-                                if behind == 0 || bitmap[tile_position as usize] == 0
-                                {
-                                    bitmap[tile_position as usize] = shade;
-                                }
-                            }
-                        }
-                    }
+                    continue;
                 }
-            }
-        }
-        else 
-        {
-            for row in 0..8
-            {
-                for index in 0..8
+                let behind = (sprite.flags & 0x80) == 0x80;
+                if let Some(palette) = object_palette(gpu_registers, row_y as i16, (sprite.flags & 0x10) == 0x10)
                 {
-                    if (y_position + row) >= 0 && (y_position + row) + 8 < 144
+                    if !behind || background == 0
                     {
-                        if (x_position + index) >= 0 && (x_position + index) < 160
-                        {
-                            let mut tile_position = ((y_position + row) * 160) + (x_position + index);
-                            let mut pixel = tile.data[(row as usize* 8) + index as usize];
-                            if pixel != 0
-                            {
-                                let shade = pixel_shade_map(pixel, palette);
-                                bitmap[tile_position as usize] = shade;
-                            }
-
-                            tile_position = ((y_position + row + 8) * 160) + (x_position + index);
-                            pixel = tile_big.data[(row as usize* 8) + index as usize];
-                            if pixel != 0
-                            {
-                                let shade = pixel_shade_map(pixel, palette);
-                                bitmap[tile_position as usize] = shade;
-                            }
-                        }
+                        bitmap[position] = pixel_shade_map(pixel, palette);
                     }
                 }
+                break;
             }
         }
     }
     return bitmap;
 }
 
+//Object palette latched for the line, None when sprites or the LCD were turned off on it
+fn object_palette(gpu_registers: &GPU_Registers, line: i16, palette_1: bool) -> Option<u8>
+{
+    let line = line as usize;
+    if (gpu_registers.lcdc_buffer[line] & 0x82) != 0x82
+    {
+        return None;
+    }
+    if palette_1
+    {
+        return Some(gpu_registers.object_palette_1_buffer[line]);
+    }
+    return Some(gpu_registers.object_palette_0_buffer[line]);
+}
 
 //Split into two
-fn pixel_shade_map(pixel_data: u8, palette_data: u8,) -> u8
+pub fn pixel_shade_map(pixel_data: u8, palette_data: u8,) -> u8
 {
     let mut pixel_shade = 0;
     match pixel_data
@@ -471,7 +563,7 @@ fn pixel_shade_map(pixel_data: u8, palette_data: u8,) -> u8
     }
 }
 
-fn pixel_color_map(pixel_shade: u8, shade_profile: &ShadeProfile) -> Rgba<u8>
+pub fn pixel_color_map(pixel_shade: u8, shade_profile: &ShadeProfile) -> Rgba<u8>
 {
     match pixel_shade 
     {
@@ -489,6 +581,7 @@ mod gpu_tests
 {
     use system::get_system_data;
     use gpu::gpu::*;
+    use gpu::fifo::PixelFifo;
 
     #[test]
     fn get_tile_data_test() {
@@ -516,6 +609,337 @@ mod gpu_tests
         }
     }
 
+    #[test]
+    fn lcd_mode_test()
+    {
+        assert_eq!(lcd_mode(0, 0), 2);
+        assert_eq!(lcd_mode(0, 79), 2);
+        assert_eq!(lcd_mode(0, 80), 3);
+        assert_eq!(lcd_mode(143, 251), 3);
+        assert_eq!(lcd_mode(143, 252), 0);
+        assert_eq!(lcd_mode(143, 455), 0);
+        assert_eq!(lcd_mode(144, 0), 1);
+        assert_eq!(lcd_mode(153, 300), 1);
+    }
+
+    #[test]
+    fn stat_mode_interrupt_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        let mut registers = Registers::new();
+        let mut gpu_registers = GPU_Registers::new();
+        system_data.mmu.mem_map[0xFF40] = 0x91;
+        system_data.mmu.mem_map[0xFF45] = 0xFF;
+        //H-blank interrupt only
+        system_data.mmu.mem_map[0xFF41] = 0x08;
+        system_data.cycles = 4;
+        let mut h_blank_requests = 0;
+        let mut modes = Vec::new();
+        for _ in 0..(456 * 2 / 4)
+        {
+            update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+            let mode = system_data.mmu.mem_map[0xFF41] & 0x03;
+            if modes.last() != Some(&mode)
+            {
+                modes.push(mode);
+            }
+            if (system_data.mmu.mem_map[0xFF0F] & 0x02) == 0x02
+            {
+                h_blank_requests += 1;
+                system_data.mmu.mem_map[0xFF0F] &= 0xFD;
+            }
+        }
+        assert_eq!(modes, vec![2, 3, 0, 2, 3, 0, 2]);
+        assert_eq!(h_blank_requests, 2);
+        assert_eq!(system_data.mmu.mem_map[0xFF41] & 0xFC, 0x08);
+    }
+
+    #[test]
+    fn pixel_fifo_stat_test()
+    {
+        //The FIFO only changes how long mode 3 lasts, not the order of modes or STAT requests
+        for &use_fifo in [false, true].iter()
+        {
+            let mut system_data : SystemData = get_system_data("CLASSIC");
+            let mut registers = Registers::new();
+            let mut gpu_registers = GPU_Registers::new();
+            if use_fifo
+            {
+                gpu_registers.pixel_fifo = Some(PixelFifo::new());
+            }
+            system_data.mmu.mem_map[0xFF40] = 0x91;
+            system_data.mmu.mem_map[0xFF45] = 0xFF;
+            system_data.mmu.mem_map[0xFF41] = 0x08;
+            system_data.cycles = 4;
+            let mut h_blank_requests = 0;
+            let mut modes = Vec::new();
+            for _ in 0..(456 * 3 / 4)
+            {
+                update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+                let mode = system_data.mmu.mem_map[0xFF41] & 0x03;
+                if modes.last() != Some(&mode)
+                {
+                    modes.push(mode);
+                }
+                if (system_data.mmu.mem_map[0xFF0F] & 0x02) == 0x02
+                {
+                    h_blank_requests += 1;
+                    system_data.mmu.mem_map[0xFF0F] &= 0xFD;
+                }
+            }
+            assert_eq!(modes, vec![2, 3, 0, 2, 3, 0, 2, 3, 0, 2]);
+            assert_eq!(h_blank_requests, 3);
+        }
+    }
+
+    #[test]
+    fn lcd_off_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        let mut registers = Registers::new();
+        let mut gpu_registers = GPU_Registers::new();
+        //OAM search interrupt only
+        system_data.mmu.mem_map[0xFF40] = 0x91;
+        system_data.mmu.mem_map[0xFF45] = 0xFF;
+        system_data.mmu.mem_map[0xFF41] = 0x20;
+        system_data.cycles = 4;
+        for _ in 0..(456 + 200) / 4
+        {
+            update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+        }
+        assert_eq!(system_data.mmu.mem_map[0xFF44], 1);
+
+        //Switched off in the middle of line 1, LY drops to 0 and stays there in mode 0
+        system_data.mmu.mem_map[0xFF40] = 0x11;
+        system_data.mmu.mem_map[0xFF0F] = 0x00;
+        for _ in 0..70224 / 4
+        {
+            update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+            assert_eq!(system_data.mmu.mem_map[0xFF44], 0);
+            assert_eq!(system_data.mmu.mem_map[0xFF41] & 0x03, 0);
+        }
+        assert_eq!(system_data.mmu.mem_map[0xFF0F], 0x00);
+        //The frontend keeps getting frames while the screen is dark
+        assert!(gpu_registers.v_blank_draw_flag);
+
+        //Switching back on starts line 0 from its first dot with OAM search
+        system_data.mmu.mem_map[0xFF40] = 0x91;
+        update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+        assert_eq!(system_data.mmu.mem_map[0xFF44], 0);
+        assert_eq!(gpu_registers.lcd_position.ly_register.sub_cycle_count, 4);
+        assert_eq!(system_data.mmu.mem_map[0xFF41] & 0x03, 2);
+        assert_eq!(system_data.mmu.mem_map[0xFF0F] & 0x02, 0x02);
+        for _ in 0..(456 - 4) / 4
+        {
+            update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+        }
+        assert_eq!(system_data.mmu.mem_map[0xFF44], 1);
+    }
+
+    #[test]
+    fn first_line_latch_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        let mut registers = Registers::new();
+        let mut gpu_registers = GPU_Registers::new();
+        system_data.mmu.mem_map[0xFF40] = 0x91;
+        system_data.mmu.mem_map[0xFF47] = 0xE4;
+        system_data.mmu.mem_map[0xFF43] = 0x12;
+        system_data.cycles = 4;
+        update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+        assert_eq!(gpu_registers.lcdc_buffer[0], 0x91);
+        assert_eq!(gpu_registers.background_palette_buffer[0], 0xE4);
+        assert_eq!(gpu_registers.lcd_position.scroll_x_buffer[0], 0x12);
+
+        //Switching the LCD back on latches line 0 again
+        system_data.mmu.mem_map[0xFF40] = 0x11;
+        update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+        system_data.mmu.mem_map[0xFF40] = 0x99;
+        system_data.mmu.mem_map[0xFF47] = 0x1B;
+        update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+        assert_eq!(gpu_registers.lcdc_buffer[0], 0x99);
+        assert_eq!(gpu_registers.background_palette_buffer[0], 0x1B);
+    }
+
+    #[test]
+    fn per_line_registers_test()
+    {
+        let mut system_data = get_system_data(&String::from("CLASSIC"));
+        let mut gpu_registers = GPU_Registers::new();
+        //Tile 1 is all colour 3 and fills the 0x9C00 map
+        for i in 0..16
+        {
+            system_data.mmu.mem_map[0x8010 + i] = 0xFF;
+        }
+        for i in 0..1024
+        {
+            system_data.mmu.mem_map[0x9C00 + i] = 1;
+        }
+        for line in 0..144
+        {
+            gpu_registers.lcdc_buffer[line] = if line < 100 { 0x91 } else { 0x99 };
+            gpu_registers.background_palette_buffer[line] = if line < 50 { 0xE4 } else { 0x1B };
+        }
+        let image = create_background_img(&gpu_registers, &mut system_data, &OAM_Table::new(), &TileMap::new());
+        let profile = &gpu_registers.shade_profile;
+        assert_eq!(*image.get_pixel(0, 10), profile.shade_0);
+        assert_eq!(*image.get_pixel(0, 60), profile.shade_3);
+        assert_eq!(*image.get_pixel(0, 120), profile.shade_0);
+
+        //Turning the background off part way down blanks only the later lines
+        gpu_registers.background_palette_buffer[130] = 0xFF;
+        gpu_registers.lcdc_buffer[130] = 0x90;
+        let image = create_background_img(&gpu_registers, &mut system_data, &OAM_Table::new(), &TileMap::new());
+        assert_eq!(*image.get_pixel(0, 130), profile.shade_0);
+    }
+
+    #[test]
+    fn sprite_selection_test()
+    {
+        let mut oam_table = OAM_Table::new();
+        //Eleven sprites on line 0, the last one is never picked
+        for i in 0..11
+        {
+            oam_table.table[i].update_sprite(16, 8 + (i as u8 * 8), 0, 0);
+        }
+        //Sprite 12 overlaps sprite 0 from the left, sprite 13 shares its X
+        oam_table.table[12].update_sprite(40, 20, 0, 0);
+        oam_table.table[13].update_sprite(40, 20, 0, 0);
+        oam_table.table[14].update_sprite(40, 24, 0, 0);
+        assert_eq!(line_sprites(&oam_table, 0, false), (0..10).collect::<Vec<usize>>());
+        assert_eq!(line_sprites(&oam_table, 24, false), vec![12, 13, 14]);
+        assert_eq!(line_sprites(&oam_table, 32, false), vec![]);
+        assert_eq!(line_sprites(&oam_table, 32, true), vec![12, 13, 14]);
+    }
+
+    #[test]
+    fn sprite_priority_test()
+    {
+        let mut gpu_registers = GPU_Registers::new();
+        for line in 0..144
+        {
+            gpu_registers.lcdc_buffer[line] = 0x97;
+            gpu_registers.background_palette_buffer[line] = 0xE4;
+            gpu_registers.object_palette_0_buffer[line] = 0xE4;
+            gpu_registers.object_palette_1_buffer[line] = 0x55;
+        }
+        let mut tile_map = TileMap::new();
+        tile_map.tiles[2].data = vec![3; 64];
+        tile_map.tiles[3].data = vec![2; 64];
+        let mut oam_table = OAM_Table::new();
+        //8x16 sprite with the priority bit over a background that is colour 0 on the left
+        oam_table.table[0].update_sprite(16, 8, 2, 0x80);
+        //Two overlapping sprites, the one further left is drawn on top despite its OAM index
+        oam_table.table[1].update_sprite(56, 20, 2, 0x10);
+        oam_table.table[2].update_sprite(56, 16, 2, 0x00);
+        let mut background = vec![0; 0x5A00];
+        for row_y in 0..144
+        {
+            for row_x in 4..160
+            {
+                background[(row_y * 160) + row_x] = 1;
+            }
+        }
+        let bitmap = apply_oam_table_to_bitmap(&oam_table, background, &gpu_registers, &tile_map);
+        assert_eq!(bitmap[0], 3);
+        assert_eq!(bitmap[4], 1);
+        assert_eq!(bitmap[(10 * 160) + 0], 2);
+        assert_eq!(bitmap[(10 * 160) + 4], 1);
+        assert_eq!(bitmap[(40 * 160) + 12], 3);
+        assert_eq!(bitmap[(40 * 160) + 17], 1);
+    }
+
+    #[test]
+    fn window_line_counter_test()
+    {
+        let mut system_data = get_system_data(&String::from("CLASSIC"));
+        let mut gpu_registers = GPU_Registers::new();
+        //Window map rows alternate between tile 0 and tile 1, tile 1 is colour 3 with a colour 1 first column
+        for i in 0..8
+        {
+            system_data.mmu.mem_map[0x8010 + (i * 2)] = 0x7F;
+            system_data.mmu.mem_map[0x8011 + (i * 2)] = 0xFF;
+        }
+        for i in 0..32
+        {
+            system_data.mmu.mem_map[0x9C20 + i] = 1;
+        }
+        for line in 0..144
+        {
+            gpu_registers.lcdc_buffer[line] = 0xF1;
+            gpu_registers.lcd_position.window_x_buffer[line] = 7;
+            gpu_registers.lcd_position.window_y_buffer[line] = 10;
+        }
+        //Hidden for lines 14-19, the window resumes with its fifth row on line 20
+        for line in 14..20
+        {
+            gpu_registers.lcdc_buffer[line] = 0xD1;
+        }
+        let mut tile_bitmaps = TileBitmaps::new();
+        let bitmap = place_window(vec![0; 0x5A00], &mut tile_bitmaps, &mut system_data, &gpu_registers);
+        assert_eq!(bitmap[(9 * 160) + 1], 0);
+        assert_eq!(bitmap[(13 * 160) + 1], 0);
+        assert_eq!(bitmap[(23 * 160) + 1], 0);
+        assert_eq!(bitmap[(24 * 160) + 1], 3);
+        assert_eq!(bitmap[(24 * 160) + 0], 2);
+
+        //WX below 7 cuts off the left columns and WX 166 shows only the last pixel, then covers all
+        //of the next line even though its WX of 167 would hide the window
+        gpu_registers.lcd_position.window_x_buffer[24] = 6;
+        gpu_registers.lcd_position.window_x_buffer[25] = 166;
+        gpu_registers.lcd_position.window_x_buffer[26] = 167;
+        gpu_registers.lcd_position.window_x_buffer[27] = 167;
+        let bitmap = place_window(vec![0; 0x5A00], &mut tile_bitmaps, &mut system_data, &gpu_registers);
+        assert_eq!(bitmap[(24 * 160) + 0], 3);
+        assert_eq!(bitmap[(25 * 160) + 158], 0);
+        assert_eq!(bitmap[(25 * 160) + 159], 2);
+        assert_eq!(bitmap[(26 * 160) + 0], 2);
+        assert_eq!(bitmap[(26 * 160) + 1], 3);
+        assert_eq!(bitmap[(26 * 160) + 159], 3);
+        assert_eq!(bitmap[(27 * 160) + 1], 0);
+        //Line 26 drew the window but line 27 did not, so window row 16 moves down from line 32 to line 33
+        assert_eq!(bitmap[(32 * 160) + 1], 3);
+        assert_eq!(bitmap[(33 * 160) + 1], 0);
+    }
+
+    #[test]
+    fn stat_shared_line_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        let mut registers = Registers::new();
+        let mut gpu_registers = GPU_Registers::new();
+        //LYC=1 and h-blank on line 0 run straight into each other, so only one request
+        system_data.mmu.mem_map[0xFF40] = 0x91;
+        system_data.mmu.mem_map[0xFF45] = 0x01;
+        system_data.mmu.mem_map[0xFF41] = 0x48;
+        system_data.cycles = 4;
+        let mut requests = 0;
+        for _ in 0..(456 / 4) + 20
+        {
+            update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+            if (system_data.mmu.mem_map[0xFF0F] & 0x02) == 0x02
+            {
+                requests += 1;
+                system_data.mmu.mem_map[0xFF0F] &= 0xFD;
+            }
+        }
+        assert_eq!(gpu_registers.lcd_position.ly_register.value, 1);
+        assert_eq!(requests, 1);
+
+        //V-blank source fires once on entering line 144
+        system_data.mmu.mem_map[0xFF41] = 0x10;
+        let mut v_blank_line = None;
+        for _ in 0..(456 * 144 / 4)
+        {
+            update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
+            if (system_data.mmu.mem_map[0xFF0F] & 0x02) == 0x02 && v_blank_line.is_none()
+            {
+                v_blank_line = Some(gpu_registers.lcd_position.ly_register.value);
+            }
+        }
+        assert_eq!(v_blank_line, Some(144));
+    }
+
     #[test]
     fn get_tile_map_test() {
         let mut system_data : SystemData = get_system_data(&String::from("CLASSIC"));
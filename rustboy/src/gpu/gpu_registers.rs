@@ -1,4 +1,5 @@
 use system::SystemData;
+use gpu::fifo::PixelFifo;
 use image::{RgbaImage, Rgba};
 
 pub struct GPU_Registers
@@ -9,6 +10,19 @@ pub struct GPU_Registers
     pub lcdc_register: LCDC_Register,
     pub lcdc_status: LCDC_Status,
     pub shade_profile: ShadeProfile,
+    //STAT interrupt sources OR'd together, IF bit 1 is only requested when this goes high
+    pub stat_interrupt_line: bool,
+    //Set to render with the pixel FIFO instead of composing the frame at v-blank
+    pub pixel_fifo: Option<PixelFifo>,
+    //LCDC.7 as last seen, line timing restarts from line 0 when it is set again.
+    //Starts clear so the first update after a reset latches line 0 the same way.
+    pub lcd_enabled: bool,
+    //LCDC and palettes latched at the start of each visible line, indexed by LY.
+    //Tile data is not latched, the frame renderer reads it from VRAM at v-blank.
+    pub lcdc_buffer: Vec<u8>,
+    pub background_palette_buffer: Vec<u8>,
+    pub object_palette_0_buffer: Vec<u8>,
+    pub object_palette_1_buffer: Vec<u8>,
 }
 
 impl GPU_Registers
@@ -23,6 +37,13 @@ impl GPU_Registers
             lcdc_register: LCDC_Register::new(),
             lcdc_status: LCDC_Status::new(),
             shade_profile: ShadeProfile::new(0),
+            stat_interrupt_line: false,
+            pixel_fifo: None,
+            lcd_enabled: false,
+            lcdc_buffer: vec![0; 144],
+            background_palette_buffer: vec![0; 144],
+            object_palette_0_buffer: vec![0; 144],
+            object_palette_1_buffer: vec![0; 144],
         }
     }
 }
@@ -60,7 +81,7 @@ impl LCD_Position
         }
     }
 
-    pub fn update(&mut self, system_data: &mut SystemData, interrupt_enabled: bool)
+    pub fn update(&mut self, system_data: &mut SystemData)
     {
         self.scroll_x = system_data.mmu.mem_map[0xFF43];
         self.scroll_y = system_data.mmu.mem_map[0xFF42];
@@ -70,18 +91,10 @@ impl LCD_Position
         if self.ly_compare == self.ly_register.value
         {
             system_data.mmu.mem_map[0xFF41] |= 0x04;
-            if interrupt_enabled
-            {
-                system_data.mmu.mem_map[0xFF0F] |= 0x02;
-            }
         }
         else 
         {
             system_data.mmu.mem_map[0xFF41] &= 0xFB;
-            if interrupt_enabled
-            {
-                system_data.mmu.mem_map[0xFF0F] &= 0xFD;
-            }
         }
     }
 }
@@ -118,9 +131,12 @@ impl LY_Register
         return false;
     }
 
+    //Back to the start of line 0, used when the LCD is switched off
     pub fn reset(&mut self, system_data: &mut SystemData)
     {
-        self.value == 0;
+        self.value = 0;
+        self.cycle_count = 0;
+        self.sub_cycle_count = 0;
         system_data.mmu.mem_map[0xFF44] = self.value;
     }
 
@@ -258,6 +274,15 @@ impl LCDC_Status
 
         self.mode_flag = self.value &0b00000011;
     }
+
+    //Whether any enabled STAT source is active for the current mode and LY=LYC state
+    pub fn interrupt_line(&self) -> bool
+    {
+        return (self.mode_0_h_blank_interrupt && self.mode_flag == 0)
+            || (self.mode_1_v_blank_interrupt && self.mode_flag == 1)
+            || (self.mode_2_oam_interrupt && self.mode_flag == 2)
+            || (self.lyc_ly_coincidence_interrupt && self.coincidence_flag);
+    }
 }
 
 pub struct ShadeProfile
@@ -299,6 +324,33 @@ impl ShadeProfile
                 shade_3: Rgba([0, 0, 0, 0xFF]),
                 default: Rgba([0, 0, 0, 0xFF]),
             },
+            //Game Boy Pocket grey scale
+            3 => return ShadeProfile
+            {
+                shade_0: Rgba([224, 224, 224, 0xFF]),
+                shade_1: Rgba([160, 160, 160, 0xFF]),
+                shade_2: Rgba([88, 88, 88, 0xFF]),
+                shade_3: Rgba([24, 24, 24, 0xFF]),
+                default: Rgba([0, 0, 0, 0xFF]),
+            },
+            //Super Game Boy default palette 1-A
+            4 => return ShadeProfile
+            {
+                shade_0: Rgba([0xF7, 0xE7, 0xC6, 0xFF]),
+                shade_1: Rgba([0xD6, 0x8E, 0x49, 0xFF]),
+                shade_2: Rgba([0xA6, 0x37, 0x25, 0xFF]),
+                shade_3: Rgba([0x33, 0x1E, 0x50, 0xFF]),
+                default: Rgba([0, 0, 0, 0xFF]),
+            },
+            //Game Boy Color palette for DMG games without a title match
+            5 => return ShadeProfile
+            {
+                shade_0: Rgba([0xFF, 0xFF, 0xFF, 0xFF]),
+                shade_1: Rgba([0x7B, 0xFF, 0x31, 0xFF]),
+                shade_2: Rgba([0x00, 0x63, 0xC5, 0xFF]),
+                shade_3: Rgba([0x00, 0x00, 0x00, 0xFF]),
+                default: Rgba([0, 0, 0, 0xFF]),
+            },
             _ => return ShadeProfile
             {
                 shade_0: Rgba([0, 0, 0, 0xFF]),
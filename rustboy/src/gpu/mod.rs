@@ -1,3 +1,4 @@
 pub mod gpu;
 pub mod gpu_registers;
+pub mod fifo;
 mod gpu_tests;
\ No newline at end of file
@@ -0,0 +1,156 @@
+use gameboy::GameBoy;
+use harness::TestResult;
+
+//Blargg's ROMs print their results over the serial port and finish with "Passed" or "Failed"
+
+pub struct BlarggReport
+{
+    pub result: TestResult,
+    pub output: String,
+    pub cycles: u64,
+}
+
+pub fn check_serial_output(output: &str) -> Option<TestResult>
+{
+    if output.contains("Passed")
+    {
+        return Some(TestResult::Passed);
+    }
+    if output.contains("Failed")
+    {
+        return Some(TestResult::Failed);
+    }
+    return None;
+}
+
+pub fn run_blargg_rom(file_name: &str, timeout_seconds: u64) -> BlarggReport
+{
+    let mut gameboy = GameBoy::new();
+    gameboy.system_data.serial.capture_output = true;
+    if let Err(error) = gameboy.load_rom(file_name)
+    {
+        return BlarggReport
+        {
+            result: TestResult::Failed,
+            output: format!("{}", error),
+            cycles: 0,
+        };
+    }
+    let timeout_cycles = timeout_seconds * gameboy.system_data.clock_speed as u64;
+    let mut checked_length = 0;
+
+    while gameboy.cycle_count < timeout_cycles
+    {
+        gameboy.step_instruction();
+        let serial_output = &gameboy.system_data.serial.output;
+        if serial_output.len() != checked_length
+        {
+            checked_length = serial_output.len();
+            let output = String::from_utf8_lossy(serial_output).into_owned();
+            if let Some(result) = check_serial_output(&output)
+            {
+                return BlarggReport
+                {
+                    result: result,
+                    output: output,
+                    cycles: gameboy.cycle_count,
+                };
+            }
+        }
+    }
+
+    return BlarggReport
+    {
+        result: TestResult::Timeout,
+        output: String::from_utf8_lossy(&gameboy.system_data.serial.output).into_owned(),
+        cycles: gameboy.cycle_count,
+    };
+}
+
+#[cfg(test)]
+mod blargg_tests
+{
+    use harness::blargg::*;
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+
+    fn assert_blargg_rom_passes(file_name: &str, timeout_seconds: u64)
+    {
+        assert!(Path::new(file_name).exists(), "Missing test ROM {}", file_name);
+        let report = run_blargg_rom(file_name, timeout_seconds);
+        assert_eq!(report.result, TestResult::Passed, "{}", report.output);
+    }
+
+    #[test]
+    fn check_serial_output_test()
+    {
+        assert_eq!(check_serial_output("cpu_instrs\n\n01:ok  02:ok"), None);
+        assert_eq!(check_serial_output("cpu_instrs\n\nPassed all tests\n"), Some(TestResult::Passed));
+        assert_eq!(check_serial_output("instr_timing\n\nFailed #255\n"), Some(TestResult::Failed));
+    }
+
+    //Sends the message one byte at a time over the internal clock, then spins
+    fn write_serial_rom(name: &str, message: &str) -> String
+    {
+        let rom_file = env::temp_dir().join(name).to_string_lossy().into_owned();
+        let mut program = Vec::new();
+        for value in message.bytes()
+        {
+            //LD A,value; LDH (01),A; LD A,$81; LDH (02),A
+            program.extend_from_slice(&[0x3E, value, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02]);
+            //LDH A,(02); BIT 7,A; JR NZ,-6 until the byte has shifted out
+            program.extend_from_slice(&[0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA]);
+        }
+        program.extend_from_slice(&[0x18, 0xFE]);
+        //JP $0150 over the cartridge header
+        let mut rom = vec![0x00; 0x8000];
+        rom[0x100..0x103].copy_from_slice(&[0xC3, 0x50, 0x01]);
+        rom[0x150..0x150 + program.len()].copy_from_slice(&program);
+        fs::write(&rom_file, &rom).unwrap();
+        return rom_file;
+    }
+
+    #[test]
+    fn generated_rom_test()
+    {
+        let rom_file = write_serial_rom("rustboy_blargg_passed_test.gb", "Passed\n");
+        let report = run_blargg_rom(&rom_file, 1);
+        assert_eq!(report.result, TestResult::Passed, "{}", report.output);
+        assert_eq!(report.output, "Passed");
+        assert!(report.cycles > 0);
+        fs::remove_file(&rom_file).unwrap();
+
+        let rom_file = write_serial_rom("rustboy_blargg_failed_test.gb", "Failed #1\n");
+        assert_eq!(run_blargg_rom(&rom_file, 1).result, TestResult::Failed);
+        fs::remove_file(&rom_file).unwrap();
+
+        let rom_file = write_serial_rom("rustboy_blargg_timeout_test.gb", "01:ok");
+        let report = run_blargg_rom(&rom_file, 1);
+        assert_eq!(report.result, TestResult::Timeout);
+        assert_eq!(report.output, "01:ok");
+        fs::remove_file(&rom_file).unwrap();
+    }
+
+    //The test ROMs are not committed, copy them into roms/ and run with cargo test -- --ignored
+    #[test]
+    #[ignore]
+    fn cpu_instrs_test()
+    {
+        assert_blargg_rom_passes("roms/cpu_instrs.gb", 120);
+    }
+
+    #[test]
+    #[ignore]
+    fn instr_timing_test()
+    {
+        assert_blargg_rom_passes("roms/instr_timing.gb", 10);
+    }
+
+    #[test]
+    #[ignore]
+    fn mem_timing_test()
+    {
+        assert_blargg_rom_passes("roms/mem_timing.gb", 10);
+    }
+}
@@ -0,0 +1,11 @@
+pub mod blargg;
+pub mod mooneye;
+pub mod screenshot;
+
+#[derive(Debug, PartialEq)]
+pub enum TestResult
+{
+    Passed,
+    Failed,
+    Timeout,
+}
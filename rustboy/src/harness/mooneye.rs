@@ -0,0 +1,108 @@
+use gameboy::GameBoy;
+use harness::TestResult;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//Mooneye ROMs finish on LD B,B with the Fibonacci numbers in B, C, D, E, H and L when they pass
+static PASS_VALUES: [u8; 6] = [3, 5, 8, 13, 21, 34];
+
+pub fn check_registers(gameboy: &GameBoy) -> TestResult
+{
+    for i in 0..PASS_VALUES.len()
+    {
+        if gameboy.registers.mapped_register_getter(i as u8 + 1) != PASS_VALUES[i]
+        {
+            return TestResult::Failed;
+        }
+    }
+    return TestResult::Passed;
+}
+
+pub fn run_until_breakpoint(gameboy: &mut GameBoy, timeout_cycles: u64) -> TestResult
+{
+    gameboy.system_data.breakpoint_flag = false;
+    while gameboy.cycle_count < timeout_cycles
+    {
+        gameboy.step_instruction();
+        if gameboy.system_data.breakpoint_flag
+        {
+            return check_registers(gameboy);
+        }
+    }
+    return TestResult::Timeout;
+}
+
+pub fn run_mooneye_rom(file_name: &str, timeout_seconds: u64) -> TestResult
+{
+    let mut gameboy = GameBoy::new();
+    if gameboy.load_rom(file_name).is_err()
+    {
+        return TestResult::Failed;
+    }
+    let timeout_cycles = timeout_seconds * gameboy.system_data.clock_speed as u64;
+    return run_until_breakpoint(&mut gameboy, timeout_cycles);
+}
+
+//Runs every .gb file in the directory, sorted by name
+pub fn run_mooneye_directory(directory: &str, timeout_seconds: u64) -> io::Result<Vec<(String, TestResult)>>
+{
+    let mut file_names: Vec<String> = Vec::new();
+    for entry in fs::read_dir(directory)?
+    {
+        let path = entry?.path();
+        if path.extension().map_or(false, |extension| extension == "gb")
+        {
+            file_names.push(path.to_string_lossy().into_owned());
+        }
+    }
+    file_names.sort();
+
+    let mut results = Vec::new();
+    for file_name in file_names
+    {
+        let result = run_mooneye_rom(&file_name, timeout_seconds);
+        let rom_name = Path::new(&file_name).file_name().unwrap().to_string_lossy().into_owned();
+        results.push((rom_name, result));
+    }
+    return Ok(results);
+}
+
+#[cfg(test)]
+mod mooneye_tests
+{
+    use harness::mooneye::*;
+
+    fn load_program(gameboy: &mut GameBoy, program: &[u8])
+    {
+        for i in 0..program.len()
+        {
+            gameboy.system_data.mmu.mem_map[0xC000 + i] = program[i];
+        }
+        gameboy.registers.program_counter = 0xC000;
+    }
+
+    #[test]
+    fn passing_registers_test()
+    {
+        let mut gameboy = GameBoy::new();
+        load_program(&mut gameboy, &[0x06, 3, 0x0E, 5, 0x16, 8, 0x1E, 13, 0x26, 21, 0x2E, 34, 0x40]);
+        assert_eq!(run_until_breakpoint(&mut gameboy, 1000), TestResult::Passed);
+    }
+
+    #[test]
+    fn failing_registers_test()
+    {
+        let mut gameboy = GameBoy::new();
+        load_program(&mut gameboy, &[0x06, 0x42, 0x48, 0x50, 0x58, 0x60, 0x68, 0x40]);
+        assert_eq!(run_until_breakpoint(&mut gameboy, 1000), TestResult::Failed);
+    }
+
+    #[test]
+    fn breakpoint_timeout_test()
+    {
+        let mut gameboy = GameBoy::new();
+        load_program(&mut gameboy, &[0x18, 0xFE]);
+        assert_eq!(run_until_breakpoint(&mut gameboy, 1000), TestResult::Timeout);
+    }
+}
@@ -0,0 +1,145 @@
+use gameboy::GameBoy;
+use gpu::gpu_registers::ShadeProfile;
+use harness::TestResult;
+use image::{self, Rgba, RgbaImage};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//dmg-acid2 and mealybug-tearoom finish on LD B,B and are judged by comparing the last
+//drawn frame against a reference screenshot in the four DMG greys
+
+//Reference images use white, light grey, dark grey and black
+pub fn reference_shade(pixel: &Rgba<u8>) -> u8
+{
+    let luma = (pixel[0] as u16 + pixel[1] as u16 + pixel[2] as u16) / 3;
+    return ((255 - luma + 42) / 85) as u8;
+}
+
+fn framebuffer_shade(pixel: &Rgba<u8>, shade_profile: &ShadeProfile) -> u8
+{
+    let shades = [shade_profile.shade_0, shade_profile.shade_1, shade_profile.shade_2, shade_profile.shade_3];
+    for i in 0..shades.len()
+    {
+        if *pixel == shades[i]
+        {
+            return i as u8;
+        }
+    }
+    return 4;
+}
+
+pub fn compare_screenshot(framebuffer: &RgbaImage, shade_profile: &ShadeProfile, expected: &RgbaImage) -> TestResult
+{
+    if framebuffer.dimensions() != expected.dimensions()
+    {
+        return TestResult::Failed;
+    }
+    for (x, y, pixel) in expected.enumerate_pixels()
+    {
+        if framebuffer_shade(framebuffer.get_pixel(x, y), shade_profile) != reference_shade(pixel)
+        {
+            return TestResult::Failed;
+        }
+    }
+    return TestResult::Passed;
+}
+
+pub fn run_screenshot_rom(file_name: &str, expected_file: &str, timeout_seconds: u64, pixel_fifo: bool) -> TestResult
+{
+    let expected = match image::open(expected_file)
+    {
+        Ok(expected) => expected.to_rgba8(),
+        Err(_) => return TestResult::Failed,
+    };
+    let mut gameboy = GameBoy::new();
+    if gameboy.load_rom(file_name).is_err()
+    {
+        return TestResult::Failed;
+    }
+    if pixel_fifo
+    {
+        gameboy.use_pixel_fifo();
+    }
+    let timeout_cycles = timeout_seconds * gameboy.system_data.clock_speed as u64;
+    gameboy.system_data.breakpoint_flag = false;
+    while gameboy.cycle_count < timeout_cycles
+    {
+        gameboy.step_instruction();
+        gameboy.poll_frame();
+        if gameboy.system_data.breakpoint_flag
+        {
+            return compare_screenshot(gameboy.framebuffer(), &gameboy.gpu_registers.shade_profile, &expected);
+        }
+    }
+    return TestResult::Timeout;
+}
+
+//Runs every .gb file in the directory against the .png of the same name, sorted by name
+pub fn run_screenshot_directory(directory: &str, timeout_seconds: u64, pixel_fifo: bool) -> io::Result<Vec<(String, TestResult)>>
+{
+    let mut file_names: Vec<String> = Vec::new();
+    for entry in fs::read_dir(directory)?
+    {
+        let path = entry?.path();
+        if path.extension().map_or(false, |extension| extension == "gb")
+        {
+            file_names.push(path.to_string_lossy().into_owned());
+        }
+    }
+    file_names.sort();
+
+    let mut results = Vec::new();
+    for file_name in file_names
+    {
+        let expected_file = Path::new(&file_name).with_extension("png").to_string_lossy().into_owned();
+        let result = run_screenshot_rom(&file_name, &expected_file, timeout_seconds, pixel_fifo);
+        let rom_name = Path::new(&file_name).file_name().unwrap().to_string_lossy().into_owned();
+        results.push((rom_name, result));
+    }
+    return Ok(results);
+}
+
+#[cfg(test)]
+mod screenshot_tests
+{
+    use harness::screenshot::*;
+    use image::ImageBuffer;
+
+    #[test]
+    fn compare_screenshot_test()
+    {
+        let shade_profile = ShadeProfile::new(0);
+        let greys = [0xFF, 0xAA, 0x55, 0x00];
+        let framebuffer: RgbaImage = ImageBuffer::from_fn(160, 144, |x, _| match x % 4
+        {
+            0 => shade_profile.shade_0,
+            1 => shade_profile.shade_1,
+            2 => shade_profile.shade_2,
+            _ => shade_profile.shade_3,
+        });
+        let mut expected: RgbaImage = ImageBuffer::from_fn(160, 144, |x, _| Rgba([greys[x as usize % 4], greys[x as usize % 4], greys[x as usize % 4], 0xFF]));
+        assert_eq!(compare_screenshot(&framebuffer, &shade_profile, &expected), TestResult::Passed);
+        expected.put_pixel(5, 5, Rgba([0x00, 0x00, 0x00, 0xFF]));
+        assert_eq!(compare_screenshot(&framebuffer, &shade_profile, &expected), TestResult::Failed);
+    }
+
+    //The test ROMs and reference images are not committed, the README lists where to get them
+    #[test]
+    #[ignore]
+    fn dmg_acid2_test()
+    {
+        assert_eq!(run_screenshot_rom("roms/dmg-acid2.gb", "roms/dmg-acid2.png", 10, false), TestResult::Passed);
+        assert_eq!(run_screenshot_rom("roms/dmg-acid2.gb", "roms/dmg-acid2.png", 10, true), TestResult::Passed);
+    }
+
+    #[test]
+    #[ignore]
+    fn mealybug_tearoom_test()
+    {
+        let results = run_screenshot_directory("roms/mealybug", 10, true).unwrap();
+        let failures: Vec<&(String, TestResult)> = results.iter().filter(|result| result.1 != TestResult::Passed).collect();
+        assert!(!results.is_empty());
+        assert!(failures.is_empty(), "{:?}", failures);
+    }
+}
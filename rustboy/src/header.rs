@@ -0,0 +1,261 @@
+use std::fmt;
+
+pub static NINTENDO_LOGO: [u8; 48] = [0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83,
+                                      0x00, 0x0C, 0x00, 0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E,
+                                      0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99, 0xBB, 0xBB, 0x67, 0x63,
+                                      0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E];
+
+//Decoded 0x0100-0x014F cartridge header
+pub struct CartridgeHeader
+{
+    pub logo: Vec<u8>,
+    pub title: String,
+    pub manufacturer_code: Option<String>,
+    pub cgb_flag: u8,
+    pub new_licensee_code: String,
+    pub sgb_flag: u8,
+    pub cartridge_type: u8,
+    pub rom_size_tag: u8,
+    pub ram_size_tag: u8,
+    pub destination_code: u8,
+    pub old_licensee_code: u8,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+    pub computed_header_checksum: u8,
+    pub computed_global_checksum: u16,
+}
+
+impl CartridgeHeader
+{
+    //Returns None if the ROM is too short to hold a header
+    pub fn parse(rom: &[u8]) -> Option<CartridgeHeader>
+    {
+        if rom.len() < 0x150
+        {
+            return None;
+        }
+
+        let cgb_flag = rom[0x0143];
+        //Newer carts shortened the title to make room for the manufacturer code and CGB flag
+        let mut title_end = 0x0144;
+        let mut manufacturer_code = None;
+        if (cgb_flag & 0x80) == 0x80
+        {
+            title_end = 0x0143;
+            let code = &rom[0x013F..0x0143];
+            if code.iter().all(|byte| (*byte as char).is_ascii_uppercase() || (*byte as char).is_ascii_digit())
+            {
+                title_end = 0x013F;
+                manufacturer_code = Some(String::from_utf8_lossy(code).into_owned());
+            }
+        }
+        let title_bytes: Vec<u8> = rom[0x0134..title_end].iter().cloned().take_while(|byte| *byte != 0).collect();
+
+        return Some(CartridgeHeader
+        {
+            logo: rom[0x0104..0x0134].to_vec(),
+            title: String::from_utf8_lossy(&title_bytes).trim_end().to_string(),
+            manufacturer_code: manufacturer_code,
+            cgb_flag: cgb_flag,
+            new_licensee_code: String::from_utf8_lossy(&rom[0x0144..0x0146]).into_owned(),
+            sgb_flag: rom[0x0146],
+            cartridge_type: rom[0x0147],
+            rom_size_tag: rom[0x0148],
+            ram_size_tag: rom[0x0149],
+            destination_code: rom[0x014A],
+            old_licensee_code: rom[0x014B],
+            version: rom[0x014C],
+            header_checksum: rom[0x014D],
+            global_checksum: ((rom[0x014E] as u16) << 8) | rom[0x014F] as u16,
+            computed_header_checksum: compute_header_checksum(rom),
+            computed_global_checksum: compute_global_checksum(rom),
+        });
+    }
+
+    pub fn logo_valid(&self) -> bool
+    {
+        return self.logo[..] == NINTENDO_LOGO[..];
+    }
+
+    pub fn header_checksum_valid(&self) -> bool
+    {
+        return self.header_checksum == self.computed_header_checksum;
+    }
+
+    pub fn global_checksum_valid(&self) -> bool
+    {
+        return self.global_checksum == self.computed_global_checksum;
+    }
+
+    pub fn cgb_support(&self) -> &str
+    {
+        match self.cgb_flag
+        {
+            0xC0 => return "CGB only",
+            0x80 => return "CGB enhanced",
+            _ => return "DMG",
+        }
+    }
+
+    pub fn sgb_support(&self) -> bool
+    {
+        return self.sgb_flag == 0x03;
+    }
+
+    pub fn destination(&self) -> &str
+    {
+        match self.destination_code
+        {
+            0x00 => return "Japan",
+            _ => return "Overseas",
+        }
+    }
+
+    //0x33 means the licensee is given by the two character new code instead
+    pub fn licensee(&self) -> String
+    {
+        if self.old_licensee_code == 0x33
+        {
+            return format!("{} (new)", self.new_licensee_code);
+        }
+        return format!("0x{:02X} (old)", self.old_licensee_code);
+    }
+
+    pub fn checksum_problems(&self) -> Vec<String>
+    {
+        let mut problems = Vec::new();
+        if !self.logo_valid()
+        {
+            problems.push(String::from("Nintendo logo does not match"));
+        }
+        if !self.header_checksum_valid()
+        {
+            problems.push(format!("Header checksum mismatch: header says 0x{:02X}, computed 0x{:02X}", self.header_checksum, self.computed_header_checksum));
+        }
+        if !self.global_checksum_valid()
+        {
+            problems.push(format!("Global checksum mismatch: header says 0x{:04X}, computed 0x{:04X}", self.global_checksum, self.computed_global_checksum));
+        }
+        return problems;
+    }
+}
+
+impl fmt::Display for CartridgeHeader
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        writeln!(f, "Title:            {}", self.title)?;
+        writeln!(f, "Manufacturer:     {}", self.manufacturer_code.as_ref().map_or("-", |code| code.as_str()))?;
+        writeln!(f, "CGB flag:         0x{:02X} ({})", self.cgb_flag, self.cgb_support())?;
+        writeln!(f, "SGB flag:         0x{:02X} ({})", self.sgb_flag, if self.sgb_support() { "SGB functions" } else { "none" })?;
+        writeln!(f, "Licensee:         {}", self.licensee())?;
+        writeln!(f, "Cartridge type:   0x{:02X}", self.cartridge_type)?;
+        writeln!(f, "ROM size:         0x{:02X}", self.rom_size_tag)?;
+        writeln!(f, "RAM size:         0x{:02X}", self.ram_size_tag)?;
+        writeln!(f, "Destination:      0x{:02X} ({})", self.destination_code, self.destination())?;
+        writeln!(f, "Version:          0x{:02X}", self.version)?;
+        writeln!(f, "Nintendo logo:    {}", if self.logo_valid() { "OK" } else { "BAD" })?;
+        writeln!(f, "Header checksum:  0x{:02X} ({})", self.header_checksum, if self.header_checksum_valid() { "OK" } else { "BAD" })?;
+        write!(f, "Global checksum:  0x{:04X} ({})", self.global_checksum, if self.global_checksum_valid() { "OK" } else { "BAD" })
+    }
+}
+
+//The boot ROM refuses to start a cartridge if this does not match 0x014D
+pub fn compute_header_checksum(rom: &[u8]) -> u8
+{
+    let mut checksum: u8 = 0;
+    for i in 0x0134..0x014D
+    {
+        checksum = checksum.wrapping_sub(rom[i]).wrapping_sub(1);
+    }
+    return checksum;
+}
+
+//Sum of every byte except the checksum itself, never checked by hardware
+pub fn compute_global_checksum(rom: &[u8]) -> u16
+{
+    let mut checksum: u16 = 0;
+    for i in 0..rom.len()
+    {
+        if i != 0x014E && i != 0x014F
+        {
+            checksum = checksum.wrapping_add(rom[i] as u16);
+        }
+    }
+    return checksum;
+}
+
+#[cfg(test)]
+mod header_tests
+{
+    use header::*;
+
+    fn build_rom() -> Vec<u8>
+    {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+        rom[0x0134..0x013F].copy_from_slice(b"POKEMON_SLV");
+        rom[0x013F..0x0143].copy_from_slice(b"AAXE");
+        rom[0x0143] = 0x80;
+        rom[0x0144..0x0146].copy_from_slice(b"01");
+        rom[0x0146] = 0x03;
+        rom[0x0147] = 0x10;
+        rom[0x0148] = 0x06;
+        rom[0x0149] = 0x03;
+        rom[0x014A] = 0x01;
+        rom[0x014B] = 0x33;
+        rom[0x014C] = 0x02;
+        rom[0x014D] = compute_header_checksum(&rom);
+        let global_checksum = compute_global_checksum(&rom);
+        rom[0x014E] = (global_checksum >> 8) as u8;
+        rom[0x014F] = global_checksum as u8;
+        return rom;
+    }
+
+    #[test]
+    fn parse_header_test()
+    {
+        let header = CartridgeHeader::parse(&build_rom()).unwrap();
+        assert_eq!(header.title, "POKEMON_SLV");
+        assert_eq!(header.manufacturer_code, Some(String::from("AAXE")));
+        assert_eq!(header.cgb_support(), "CGB enhanced");
+        assert!(header.sgb_support());
+        assert_eq!(header.licensee(), "01 (new)");
+        assert_eq!(header.cartridge_type, 0x10);
+        assert_eq!(header.rom_size_tag, 0x06);
+        assert_eq!(header.ram_size_tag, 0x03);
+        assert_eq!(header.destination(), "Overseas");
+        assert_eq!(header.version, 0x02);
+        assert!(header.logo_valid());
+        assert!(header.checksum_problems().is_empty());
+    }
+
+    #[test]
+    fn old_title_test()
+    {
+        let mut rom = vec![0; 0x150];
+        rom[0x0134..0x0140].copy_from_slice(b"TETRIS\0\0\0\0\0\0");
+        rom[0x014B] = 0x01;
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.title, "TETRIS");
+        assert_eq!(header.manufacturer_code, None);
+        assert_eq!(header.cgb_support(), "DMG");
+        assert_eq!(header.licensee(), "0x01 (old)");
+        assert_eq!(header.destination(), "Japan");
+        assert!(CartridgeHeader::parse(&rom[0..0x14F]).is_none());
+    }
+
+    #[test]
+    fn checksum_mismatch_test()
+    {
+        let mut rom = build_rom();
+        rom[0x0134] = b'Q';
+        rom[0x0105] = 0x00;
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert!(!header.logo_valid());
+        assert!(!header.header_checksum_valid());
+        assert!(!header.global_checksum_valid());
+        assert_eq!(header.checksum_problems().len(), 3);
+    }
+}
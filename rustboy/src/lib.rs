@@ -0,0 +1,26 @@
+#![allow(dead_code)]
+
+extern crate csv;
+extern crate hex;
+extern crate image;
+extern crate flate2;
+extern crate crc32fast;
+extern crate zip;
+
+pub mod cpu;
+pub mod gpu;
+pub mod system;
+pub mod model;
+pub mod mmu;
+pub mod mapper;
+pub mod header;
+pub mod loader;
+pub mod rtc;
+pub mod timer;
+pub mod serial;
+pub mod apu;
+pub mod link;
+pub mod gameboy;
+pub mod harness;
+
+pub use gameboy::GameBoy;
@@ -0,0 +1,169 @@
+use std::io;
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+
+//Both sides stop and exchange messages every SYNC_CYCLES, so neither can run
+//more than one slice ahead of the other. Transfers started in a slice are
+//answered in the next one, well inside the 4096 cycles a byte takes to shift.
+//Transfers carry how far into the slice they started, so the receiving side
+//can finish on the same cycle as the side driving the clock.
+pub static SYNC_CYCLES: u32 = 1024;
+
+static TRANSFER_MESSAGE: u8 = 0x01;
+static REPLY_MESSAGE: u8 = 0x02;
+static SYNC_MESSAGE: u8 = 0x03;
+
+#[derive(Debug, PartialEq)]
+pub enum LinkMessage
+{
+    //Value and the cycle in the sender's slice the transfer started on
+    Transfer(u8, u16),
+    Reply(u8),
+}
+
+pub struct LinkCable
+{
+    stream: TcpStream,
+    pub slice_cycles: u32,
+    outgoing: Vec<u8>,
+}
+
+impl LinkCable
+{
+    //Waits on localhost for the other emulator to connect
+    pub fn host(port: u16) -> io::Result<LinkCable>
+    {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        return LinkCable::accept(&listener);
+    }
+
+    pub fn accept(listener: &TcpListener) -> io::Result<LinkCable>
+    {
+        let (stream, _) = listener.accept()?;
+        return LinkCable::from_stream(stream);
+    }
+
+    pub fn connect(address: &str) -> io::Result<LinkCable>
+    {
+        let stream = if address.contains(':')
+        {
+            TcpStream::connect(address)?
+        }
+        else
+        {
+            TcpStream::connect(("127.0.0.1", address.parse::<u16>().map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid link port"))?))?
+        };
+        return LinkCable::from_stream(stream);
+    }
+
+    pub fn from_stream(stream: TcpStream) -> io::Result<LinkCable>
+    {
+        stream.set_nodelay(true)?;
+        return Ok(LinkCable
+        {
+            stream: stream,
+            slice_cycles: 0,
+            outgoing: Vec::new(),
+        });
+    }
+
+    pub fn send_transfer(&mut self, value: u8)
+    {
+        let slice_cycles = self.slice_cycles as u16;
+        self.push_message(TRANSFER_MESSAGE, value, slice_cycles);
+    }
+
+    pub fn send_reply(&mut self, value: u8)
+    {
+        self.push_message(REPLY_MESSAGE, value, 0);
+    }
+
+    //Every message is a tag, a value and a 16-bit cycle offset
+    fn push_message(&mut self, tag: u8, value: u8, cycles: u16)
+    {
+        self.outgoing.extend_from_slice(&[tag, value, cycles as u8, (cycles >> 8) as u8]);
+    }
+
+    //Returns the other side's messages once per slice, blocking until it has caught up
+    pub fn add_cycles(&mut self, cycles: u8) -> io::Result<Vec<LinkMessage>>
+    {
+        let mut messages = Vec::new();
+        self.slice_cycles += cycles as u32;
+        while self.slice_cycles >= SYNC_CYCLES
+        {
+            self.slice_cycles -= SYNC_CYCLES;
+            messages.extend(self.sync()?);
+        }
+        return Ok(messages);
+    }
+
+    fn sync(&mut self) -> io::Result<Vec<LinkMessage>>
+    {
+        self.push_message(SYNC_MESSAGE, 0, 0);
+        self.stream.write_all(&self.outgoing)?;
+        self.outgoing.clear();
+
+        let mut messages = Vec::new();
+        let mut message = [0; 4];
+        loop
+        {
+            self.stream.read_exact(&mut message)?;
+            match message[0]
+            {
+                tag if tag == TRANSFER_MESSAGE => messages.push(LinkMessage::Transfer(message[1], message[2] as u16 | (message[3] as u16) << 8)),
+                tag if tag == REPLY_MESSAGE => messages.push(LinkMessage::Reply(message[1])),
+                tag if tag == SYNC_MESSAGE => return Ok(messages),
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown link message")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod link_tests
+{
+    use link::*;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    fn linked_pair() -> (LinkCable, LinkCable)
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let client = thread::spawn(move || LinkCable::from_stream(TcpStream::connect(address).unwrap()).unwrap());
+        let host = LinkCable::accept(&listener).unwrap();
+        return (host, client.join().unwrap());
+    }
+
+    #[test]
+    fn sync_exchanges_messages_test()
+    {
+        let (mut host, mut client) = linked_pair();
+        let client_thread = thread::spawn(move ||
+        {
+            client.send_reply(0x34);
+            let first = client.add_cycles(200).unwrap();
+            let mut second = Vec::new();
+            for _ in 0..8
+            {
+                second.extend(client.add_cycles(128).unwrap());
+            }
+            (first, second)
+        });
+
+        host.add_cycles(100).unwrap();
+        host.send_transfer(0x12);
+        let messages = host.add_cycles(155).unwrap();
+        assert!(messages.is_empty());
+        let mut messages = Vec::new();
+        for _ in 0..4
+        {
+            messages.extend(host.add_cycles(255).unwrap());
+        }
+        assert_eq!(messages, vec![LinkMessage::Reply(0x34)]);
+
+        let (first, second) = client_thread.join().unwrap();
+        assert!(first.is_empty());
+        assert_eq!(second, vec![LinkMessage::Transfer(0x12, 100)]);
+    }
+}
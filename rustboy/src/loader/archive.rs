@@ -0,0 +1,140 @@
+use std::io::prelude::*;
+use std::io::Cursor;
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+use loader::{LoadError, MAX_ROM_SIZE};
+
+static GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+static ZIP_MAGIC: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+
+//Returns the ROM inside a gzip or zip container, anything else is passed through untouched
+pub fn unpack(data: Vec<u8>) -> Result<Vec<u8>, LoadError>
+{
+    if data.starts_with(&GZIP_MAGIC)
+    {
+        return unpack_gzip(&data);
+    }
+    if data.starts_with(&ZIP_MAGIC)
+    {
+        return unpack_zip(data);
+    }
+    return Ok(data);
+}
+
+fn unpack_gzip(data: &[u8]) -> Result<Vec<u8>, LoadError>
+{
+    return read_limited(GzDecoder::new(data), "bad gzip stream");
+}
+
+//Reads one byte past the limit so an oversized entry is caught without inflating all of it
+fn read_limited<R: Read>(reader: R, context: &str) -> Result<Vec<u8>, LoadError>
+{
+    let mut rom: Vec<u8> = Vec::new();
+    if let Err(error) = reader.take(MAX_ROM_SIZE as u64 + 1).read_to_end(&mut rom)
+    {
+        return Err(LoadError::Archive(format!("{}: {}", context, error)));
+    }
+    if rom.len() > MAX_ROM_SIZE
+    {
+        return Err(LoadError::ArchiveTooLarge);
+    }
+    return Ok(rom);
+}
+
+//Loads the first .gb or .gbc entry in the archive
+fn unpack_zip(data: Vec<u8>) -> Result<Vec<u8>, LoadError>
+{
+    let mut archive = match ZipArchive::new(Cursor::new(data))
+    {
+        Ok(archive) => archive,
+        Err(error) => return Err(LoadError::Archive(format!("bad zip file: {}", error))),
+    };
+    for i in 0..archive.len()
+    {
+        let mut entry = match archive.by_index(i)
+        {
+            Ok(entry) => entry,
+            Err(error) => return Err(LoadError::Archive(format!("bad zip entry: {}", error))),
+        };
+        if !is_rom_name(entry.name())
+        {
+            continue;
+        }
+        let context = format!("could not extract {}", entry.name());
+        return read_limited(entry, &context);
+    }
+    return Err(LoadError::NoRomInArchive);
+}
+
+fn is_rom_name(name: &str) -> bool
+{
+    let name = name.to_lowercase();
+    return name.ends_with(".gb") || name.ends_with(".gbc");
+}
+
+#[cfg(test)]
+mod archive_tests
+{
+    use loader::archive::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+
+    fn build_zip(entries: &[(&str, &[u8])]) -> Vec<u8>
+    {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for &(name, contents) in entries
+        {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        return writer.finish().unwrap().into_inner();
+    }
+
+    #[test]
+    fn plain_rom_test()
+    {
+        let rom = vec![0x00, 0xC3, 0x50, 0x01];
+        assert_eq!(unpack(rom.clone()), Ok(rom));
+    }
+
+    #[test]
+    fn gzip_test()
+    {
+        let rom: Vec<u8> = (0..0x8000).map(|i| (i % 251) as u8).collect();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&rom).unwrap();
+        assert_eq!(unpack(encoder.finish().unwrap()), Ok(rom));
+
+        assert!(unpack(vec![0x1F, 0x8B, 0x00]).is_err());
+    }
+
+    #[test]
+    fn zip_test()
+    {
+        let archive = build_zip(&[("readme.txt", b"not a rom"), ("Tetris.GB", b"first"), ("tetris.gbc", b"second")]);
+        assert_eq!(unpack(archive), Ok(b"first".to_vec()));
+
+        let archive = build_zip(&[("readme.txt", b"not a rom")]);
+        assert_eq!(unpack(archive), Err(LoadError::NoRomInArchive));
+    }
+
+    #[test]
+    fn oversized_entry_test()
+    {
+        let rom = vec![0; MAX_ROM_SIZE];
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&rom).unwrap();
+        assert_eq!(unpack(encoder.finish().unwrap()).map(|rom| rom.len()), Ok(MAX_ROM_SIZE));
+
+        let rom = vec![0; MAX_ROM_SIZE + 1];
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&rom).unwrap();
+        assert_eq!(unpack(encoder.finish().unwrap()), Err(LoadError::ArchiveTooLarge));
+
+        let archive = build_zip(&[("huge.gb", &rom)]);
+        assert_eq!(unpack(archive), Err(LoadError::ArchiveTooLarge));
+    }
+}
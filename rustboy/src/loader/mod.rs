@@ -0,0 +1,201 @@
+pub mod archive;
+pub mod patch;
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use mapper::MapperError;
+use loader::patch::{PatchError, apply_patch};
+
+pub static BOOT_ROM_SIZE: usize = 0x100;
+//Largest ROM a cartridge header can declare
+pub static MAX_ROM_SIZE: usize = 0x800000;
+
+#[derive(Debug, PartialEq)]
+pub enum LoadError
+{
+    NotFound(String),
+    Io(String),
+    //Too short to even hold the 0x0100-0x014F header
+    Truncated(usize),
+    UnsupportedMapper(u8),
+    //Corrupt gzip or zip container
+    Archive(String),
+    NoRomInArchive,
+    //Archive entry inflates past MAX_ROM_SIZE
+    ArchiveTooLarge,
+    Patch(String, PatchError),
+    BootRomSize(usize),
+    //File is shorter than the ROM size byte at 0x0148 declares
+    SizeMismatch { expected: usize, actual: usize },
+}
+
+impl fmt::Display for LoadError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            LoadError::NotFound(ref file_name) => write!(f, "ROM file not found: {}", file_name),
+            LoadError::Io(ref message) => write!(f, "Could not read {}", message),
+            LoadError::Truncated(length) => write!(f, "ROM is truncated: {} bytes is too small to hold a cartridge header", length),
+            LoadError::UnsupportedMapper(cartridge_type) => write!(f, "Unsupported cartridge type 0x{:02X}", cartridge_type),
+            LoadError::Archive(ref message) => write!(f, "Could not unpack ROM: {}", message),
+            LoadError::NoRomInArchive => write!(f, "Archive does not contain a .gb or .gbc file"),
+            LoadError::ArchiveTooLarge => write!(f, "Archive entry is larger than the {} byte ROM limit", MAX_ROM_SIZE),
+            LoadError::Patch(ref file_name, ref error) => write!(f, "Could not apply patch {}: {}", file_name, error),
+            LoadError::BootRomSize(length) => write!(f, "Boot ROM must be {} bytes but the file has {}", BOOT_ROM_SIZE, length),
+            LoadError::SizeMismatch { expected, actual } => write!(f, "ROM is too short: the header declares {} bytes but the file has {}", expected, actual),
+        }
+    }
+}
+
+impl From<MapperError> for LoadError
+{
+    fn from(error: MapperError) -> LoadError
+    {
+        match error
+        {
+            MapperError::UnsupportedCartridgeType(cartridge_type) => return LoadError::UnsupportedMapper(cartridge_type),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RomData
+{
+    pub data: Vec<u8>,
+    //Patch that was applied, for the frontend to report
+    pub patch_file: Option<String>,
+}
+
+//Reads a ROM and applies the given patch, or one found next to the ROM.
+//Patching happens in memory, the files on disk are never touched.
+pub fn load_rom_data(file_name: &str, patch_file: Option<&str>) -> Result<RomData, LoadError>
+{
+    let rom = read_rom_file(file_name)?;
+    let patch_file = match patch_file
+    {
+        Some(patch_file) => String::from(patch_file),
+        None => match find_patch_file(file_name)
+        {
+            Some(patch_file) => patch_file,
+            None => return Ok(RomData { data: rom, patch_file: None }),
+        },
+    };
+    let patch = read_file(&patch_file)?;
+    match apply_patch(&rom, &patch)
+    {
+        Ok(patched_rom) => return Ok(RomData { data: patched_rom, patch_file: Some(patch_file) }),
+        Err(error) => return Err(LoadError::Patch(patch_file, error)),
+    }
+}
+
+//Reads a ROM from disk, unpacking it first if it is stored in a .zip or .gz
+pub fn read_rom_file(file_name: &str) -> Result<Vec<u8>, LoadError>
+{
+    return archive::unpack(read_file(file_name)?);
+}
+
+//DMG boot ROM dumps are exactly 256 bytes
+pub fn read_boot_rom(file_name: &str) -> Result<Vec<u8>, LoadError>
+{
+    let boot_rom = read_file(file_name)?;
+    if boot_rom.len() != BOOT_ROM_SIZE
+    {
+        return Err(LoadError::BootRomSize(boot_rom.len()));
+    }
+    return Ok(boot_rom);
+}
+
+fn read_file(file_name: &str) -> Result<Vec<u8>, LoadError>
+{
+    let mut file = match File::open(file_name)
+    {
+        Ok(file) => file,
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Err(LoadError::NotFound(String::from(file_name))),
+        Err(error) => return Err(LoadError::Io(format!("{}: {}", file_name, error))),
+    };
+    let mut buffer: Vec<u8> = Vec::new();
+    if let Err(error) = file.read_to_end(&mut buffer)
+    {
+        return Err(LoadError::Io(format!("{}: {}", file_name, error)));
+    }
+    return Ok(buffer);
+}
+
+//First of <rom>.ips, <rom>.ups and <rom>.bps that exists
+pub fn find_patch_file(rom_file_name: &str) -> Option<String>
+{
+    for extension in ["ips", "ups", "bps"].iter()
+    {
+        let patch_file = sibling_file_name(rom_file_name, extension);
+        if Path::new(&patch_file).exists()
+        {
+            return Some(patch_file);
+        }
+    }
+    return None;
+}
+
+//Swaps the ROM extension for another, game.gb.gz counts as game like game.gb does
+pub fn sibling_file_name(rom_file_name: &str, extension: &str) -> String
+{
+    let mut path = Path::new(rom_file_name).to_path_buf();
+    if path.extension().map_or(false, |rom_extension| rom_extension.eq_ignore_ascii_case("gz"))
+    {
+        path.set_extension("");
+    }
+    return path.with_extension(extension).to_string_lossy().into_owned();
+}
+
+#[cfg(test)]
+mod loader_tests
+{
+    use loader::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn missing_file_test()
+    {
+        assert_eq!(read_rom_file("roms/does_not_exist.gb"), Err(LoadError::NotFound(String::from("roms/does_not_exist.gb"))));
+    }
+
+    #[test]
+    fn error_message_test()
+    {
+        assert_eq!(format!("{}", LoadError::SizeMismatch { expected: 0x8000, actual: 0x4000 }), "ROM is too short: the header declares 32768 bytes but the file has 16384");
+        assert_eq!(LoadError::from(MapperError::UnsupportedCartridgeType(0xFC)), LoadError::UnsupportedMapper(0xFC));
+    }
+
+    #[test]
+    fn sibling_file_name_test()
+    {
+        assert_eq!(sibling_file_name("roms/zelda.gb", "ips"), "roms/zelda.ips");
+        assert_eq!(sibling_file_name("roms/zelda.gb.gz", "bps"), "roms/zelda.bps");
+        assert_eq!(sibling_file_name("roms/zelda.zip", "ups"), "roms/zelda.ups");
+    }
+
+    #[test]
+    fn patch_next_to_rom_test()
+    {
+        let directory = env::temp_dir();
+        let rom_file = directory.join("rustboy_patch_next_to_rom_test.gb").to_string_lossy().into_owned();
+        let patch_file = directory.join("rustboy_patch_next_to_rom_test.ips").to_string_lossy().into_owned();
+        fs::write(&rom_file, &[0x00, 0x01, 0x02, 0x03]).unwrap();
+        fs::write(&patch_file, b"PATCH\x00\x00\x01\x00\x02\xAA\xBBEOF").unwrap();
+
+        assert_eq!(find_patch_file(&rom_file), Some(patch_file.clone()));
+        assert_eq!(load_rom_data(&rom_file, None), Ok(RomData { data: vec![0x00, 0xAA, 0xBB, 0x03], patch_file: Some(patch_file.clone()) }));
+        assert_eq!(fs::read(&rom_file).unwrap(), vec![0x00, 0x01, 0x02, 0x03]);
+
+        fs::write(&patch_file, b"NOTAPATCH").unwrap();
+        assert_eq!(load_rom_data(&rom_file, None), Err(LoadError::Patch(patch_file.clone(), PatchError::UnknownFormat)));
+        assert_eq!(load_rom_data(&rom_file, Some("roms/missing.ips")), Err(LoadError::NotFound(String::from("roms/missing.ips"))));
+        fs::remove_file(&rom_file).unwrap();
+        fs::remove_file(&patch_file).unwrap();
+    }
+}
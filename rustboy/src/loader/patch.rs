@@ -0,0 +1,511 @@
+use std::fmt;
+use crc32fast::Hasher;
+
+#[derive(Debug, PartialEq)]
+pub enum PatchError
+{
+    UnknownFormat,
+    //Patch ended in the middle of a record
+    Truncated,
+    //UPS and BPS patches record the size and CRC32 of the ROM they were made against
+    WrongSourceSize { expected: usize, actual: usize },
+    ChecksumMismatch { part: &'static str, expected: u32, actual: u32 },
+    //Offset or length outside the ROM or past the target size
+    OutOfBounds,
+}
+
+impl fmt::Display for PatchError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            PatchError::UnknownFormat => write!(f, "not an IPS, UPS or BPS patch"),
+            PatchError::Truncated => write!(f, "patch is truncated"),
+            PatchError::WrongSourceSize { expected, actual } => write!(f, "patch expects a {} byte ROM but this one is {} bytes", expected, actual),
+            PatchError::ChecksumMismatch { part, expected, actual } => write!(f, "{} CRC32 mismatch: expected 0x{:08X}, got 0x{:08X}", part, expected, actual),
+            PatchError::OutOfBounds => write!(f, "patch reads or writes outside the ROM"),
+        }
+    }
+}
+
+//Largest ROM a cartridge header can declare, target sizes past it are not trusted
+static MAX_TARGET_SIZE: usize = 0x800000;
+
+//Picks the format from the magic string and returns the patched copy of the ROM
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError>
+{
+    if patch.starts_with(b"PATCH")
+    {
+        return apply_ips(rom, patch);
+    }
+    if patch.starts_with(b"UPS1")
+    {
+        return apply_ups(rom, patch);
+    }
+    if patch.starts_with(b"BPS1")
+    {
+        return apply_bps(rom, patch);
+    }
+    return Err(PatchError::UnknownFormat);
+}
+
+struct PatchReader<'a>
+{
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PatchReader<'a>
+{
+    fn new(data: &'a [u8], position: usize) -> PatchReader<'a>
+    {
+        return PatchReader
+        {
+            data: data,
+            position: position,
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError>
+    {
+        if self.position >= self.data.len()
+        {
+            return Err(PatchError::Truncated);
+        }
+        self.position += 1;
+        return Ok(self.data[self.position - 1]);
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], PatchError>
+    {
+        if self.data.len() - self.position < count
+        {
+            return Err(PatchError::Truncated);
+        }
+        self.position += count;
+        return Ok(&self.data[self.position - count..self.position]);
+    }
+
+    fn big_endian(&mut self, count: usize) -> Result<usize, PatchError>
+    {
+        let mut value = 0;
+        for byte in self.bytes(count)?
+        {
+            value = (value << 8) | *byte as usize;
+        }
+        return Ok(value);
+    }
+
+    //UPS and BPS variable length number, 7 bits per byte with the top bit ending it
+    fn number(&mut self) -> Result<usize, PatchError>
+    {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop
+        {
+            let byte = self.byte()?;
+            value = value.checked_add((byte & 0x7F) as usize * shift).ok_or(PatchError::Truncated)?;
+            if (byte & 0x80) == 0x80
+            {
+                return Ok(value);
+            }
+            shift = shift.checked_mul(0x80).ok_or(PatchError::Truncated)?;
+            value = value.checked_add(shift).ok_or(PatchError::Truncated)?;
+        }
+    }
+}
+
+fn crc32(data: &[u8]) -> u32
+{
+    let mut hasher = Hasher::new();
+    hasher.update(data);
+    return hasher.finalize();
+}
+
+fn read_u32_le(data: &[u8]) -> u32
+{
+    return data[0] as u32 | (data[1] as u32) << 8 | (data[2] as u32) << 16 | (data[3] as u32) << 24;
+}
+
+//UPS and BPS patches end with the CRC32 of the source, the target and the patch itself
+fn check_crc(patch: &[u8], index: usize, part: &'static str, data: &[u8]) -> Result<(), PatchError>
+{
+    let footer = &patch[patch.len() - 12..];
+    let expected = read_u32_le(&footer[index * 4..index * 4 + 4]);
+    let actual = crc32(data);
+    if expected != actual
+    {
+        return Err(PatchError::ChecksumMismatch { part: part, expected: expected, actual: actual });
+    }
+    return Ok(());
+}
+
+fn check_source(patch: &[u8], rom: &[u8], source_size: usize) -> Result<(), PatchError>
+{
+    if source_size != rom.len()
+    {
+        return Err(PatchError::WrongSourceSize { expected: source_size, actual: rom.len() });
+    }
+    return check_crc(patch, 0, "source", rom);
+}
+
+//Records of 24-bit offset and 16-bit length, a zero length means a run of one byte
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError>
+{
+    let mut output = rom.to_vec();
+    let mut reader = PatchReader::new(patch, 5);
+    loop
+    {
+        let offset_bytes = reader.bytes(3)?;
+        if offset_bytes == b"EOF"
+        {
+            break;
+        }
+        let offset = (offset_bytes[0] as usize) << 16 | (offset_bytes[1] as usize) << 8 | offset_bytes[2] as usize;
+        let length = reader.big_endian(2)?;
+        if length == 0
+        {
+            let run_length = reader.big_endian(2)?;
+            let value = reader.byte()?;
+            write_bytes(&mut output, offset, &vec![value; run_length]);
+        }
+        else
+        {
+            let data = reader.bytes(length)?;
+            write_bytes(&mut output, offset, data);
+        }
+    }
+    //Some patches shrink the ROM with a 24-bit length after EOF
+    if let Ok(truncate_length) = reader.big_endian(3)
+    {
+        output.truncate(truncate_length);
+    }
+    return Ok(output);
+}
+
+fn write_bytes(output: &mut Vec<u8>, offset: usize, data: &[u8])
+{
+    if output.len() < offset + data.len()
+    {
+        output.resize(offset + data.len(), 0);
+    }
+    output[offset..offset + data.len()].copy_from_slice(data);
+}
+
+//Skip counts and runs of bytes XORed into the ROM, each run ending on a zero
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError>
+{
+    if patch.len() < 16
+    {
+        return Err(PatchError::Truncated);
+    }
+    check_crc(patch, 2, "patch", &patch[0..patch.len() - 4])?;
+    let end = patch.len() - 12;
+    let mut reader = PatchReader::new(&patch[0..end], 4);
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    check_source(patch, rom, source_size)?;
+    if target_size > MAX_TARGET_SIZE
+    {
+        return Err(PatchError::OutOfBounds);
+    }
+
+    let mut output = rom.to_vec();
+    output.resize(target_size, 0);
+    let mut offset: usize = 0;
+    while reader.position < end
+    {
+        offset = offset.checked_add(reader.number()?).ok_or(PatchError::OutOfBounds)?;
+        loop
+        {
+            let value = reader.byte()?;
+            if offset < target_size
+            {
+                output[offset] ^= value;
+            }
+            offset = offset.checked_add(1).ok_or(PatchError::OutOfBounds)?;
+            if value == 0
+            {
+                break;
+            }
+        }
+    }
+    check_crc(patch, 1, "target", &output)?;
+    return Ok(output);
+}
+
+//Builds the new ROM from copies out of the old ROM, the patch, or what has been written so far
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError>
+{
+    if patch.len() < 16
+    {
+        return Err(PatchError::Truncated);
+    }
+    check_crc(patch, 2, "patch", &patch[0..patch.len() - 4])?;
+    let end = patch.len() - 12;
+    let mut reader = PatchReader::new(&patch[0..end], 4);
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+    check_source(patch, rom, source_size)?;
+    if target_size > MAX_TARGET_SIZE
+    {
+        return Err(PatchError::OutOfBounds);
+    }
+
+    let mut output: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_offset: isize = 0;
+    let mut target_offset: isize = 0;
+    while reader.position < end
+    {
+        let action = reader.number()?;
+        let length = (action >> 2) + 1;
+        let start = output.len();
+        if length > target_size - start
+        {
+            return Err(PatchError::OutOfBounds);
+        }
+        match action & 0x03
+        {
+            //Source read, copy from the same place in the old ROM
+            0 =>
+            {
+                if length > rom.len().saturating_sub(start)
+                {
+                    return Err(PatchError::OutOfBounds);
+                }
+                output.extend_from_slice(&rom[start..start + length]);
+            },
+            //Target read, bytes come straight from the patch
+            1 => output.extend_from_slice(reader.bytes(length)?),
+            //Source copy
+            2 =>
+            {
+                source_offset = move_offset(source_offset, reader.number()?)?;
+                if length > rom.len().saturating_sub(source_offset as usize)
+                {
+                    return Err(PatchError::OutOfBounds);
+                }
+                output.extend_from_slice(&rom[source_offset as usize..source_offset as usize + length]);
+                source_offset += length as isize;
+            },
+            //Target copy, may overlap what it is writing so goes byte by byte
+            _ =>
+            {
+                target_offset = move_offset(target_offset, reader.number()?)?;
+                if target_offset as usize >= output.len()
+                {
+                    return Err(PatchError::OutOfBounds);
+                }
+                for _ in 0..length
+                {
+                    let value = output[target_offset as usize];
+                    output.push(value);
+                    target_offset += 1;
+                }
+            },
+        }
+    }
+    if output.len() != target_size
+    {
+        return Err(PatchError::Truncated);
+    }
+    check_crc(patch, 1, "target", &output)?;
+    return Ok(output);
+}
+
+//Lowest bit is the sign, the rest is the distance
+fn relative_offset(value: usize) -> isize
+{
+    let distance = (value >> 1) as isize;
+    if (value & 0x01) == 0x01
+    {
+        return -distance;
+    }
+    return distance;
+}
+
+fn move_offset(offset: isize, value: usize) -> Result<isize, PatchError>
+{
+    match offset.checked_add(relative_offset(value))
+    {
+        Some(offset) if offset >= 0 => return Ok(offset),
+        _ => return Err(PatchError::OutOfBounds),
+    }
+}
+
+#[cfg(test)]
+mod patch_tests
+{
+    use loader::patch::*;
+
+    fn encode_number(output: &mut Vec<u8>, value: usize)
+    {
+        let mut value = value;
+        loop
+        {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0
+            {
+                output.push(byte | 0x80);
+                return;
+            }
+            output.push(byte);
+            value -= 1;
+        }
+    }
+
+    fn push_u32_le(output: &mut Vec<u8>, value: u32)
+    {
+        for i in 0..4
+        {
+            output.push((value >> (i * 8)) as u8);
+        }
+    }
+
+    fn finish_patch(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8>
+    {
+        push_u32_le(&mut patch, crc32(source));
+        push_u32_le(&mut patch, crc32(target));
+        let patch_crc = crc32(&patch);
+        push_u32_le(&mut patch, patch_crc);
+        return patch;
+    }
+
+    #[test]
+    fn number_test()
+    {
+        for value in [0, 1, 127, 128, 300, 0x4000, 0x123456].iter()
+        {
+            let mut encoded = Vec::new();
+            encode_number(&mut encoded, *value);
+            assert_eq!(PatchReader::new(&encoded, 0).number(), Ok(*value));
+        }
+    }
+
+    #[test]
+    fn ips_test()
+    {
+        let rom = vec![0u8; 8];
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x02, 0xAA, 0xBB]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x04, 0xCC]);
+        patch.extend_from_slice(b"EOF");
+        assert_eq!(apply_patch(&rom, &patch), Ok(vec![0, 0, 0xAA, 0xBB, 0, 0, 0xCC, 0xCC, 0xCC, 0xCC]));
+
+        patch.extend_from_slice(&[0x00, 0x00, 0x04]);
+        assert_eq!(apply_patch(&rom, &patch), Ok(vec![0, 0, 0xAA, 0xBB]));
+
+        assert_eq!(apply_patch(&rom, b"PATCH\x00\x00\x02\x00\x05\xAA"), Err(PatchError::Truncated));
+        assert_eq!(apply_patch(&rom, b"NOTAPATCH"), Err(PatchError::UnknownFormat));
+    }
+
+    #[test]
+    fn ups_test()
+    {
+        let source = vec![1u8, 2, 3, 4];
+        let target = vec![1u8, 7, 3, 4, 9];
+        let mut patch = b"UPS1".to_vec();
+        encode_number(&mut patch, source.len());
+        encode_number(&mut patch, target.len());
+        encode_number(&mut patch, 1);
+        patch.extend_from_slice(&[2 ^ 7, 0x00]);
+        encode_number(&mut patch, 1);
+        patch.extend_from_slice(&[9, 0x00]);
+        let patch = finish_patch(patch, &source, &target);
+        assert_eq!(apply_patch(&source, &patch), Ok(target));
+
+        match apply_patch(&[1, 2, 3, 5], &patch)
+        {
+            Err(PatchError::ChecksumMismatch { part, .. }) => assert_eq!(part, "source"),
+            result => panic!("expected a source checksum error, got {:?}", result),
+        }
+        assert_eq!(apply_patch(&[1, 2, 3], &patch), Err(PatchError::WrongSourceSize { expected: 4, actual: 3 }));
+
+        let mut corrupt = patch.clone();
+        corrupt[6] ^= 0x01;
+        match apply_patch(&source, &corrupt)
+        {
+            Err(PatchError::ChecksumMismatch { part, .. }) => assert_eq!(part, "patch"),
+            result => panic!("expected a patch checksum error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn bps_test()
+    {
+        let source = b"ABCDEFGH".to_vec();
+        let target = b"ABCDxyxyxyGH".to_vec();
+        let mut patch = b"BPS1".to_vec();
+        encode_number(&mut patch, source.len());
+        encode_number(&mut patch, target.len());
+        encode_number(&mut patch, 0);
+        //Source read of ABCD
+        encode_number(&mut patch, (3 << 2) | 0);
+        //Target read of xy
+        encode_number(&mut patch, (1 << 2) | 1);
+        patch.extend_from_slice(b"xy");
+        //Target copy of xyxy from offset 4, overlapping its own output
+        encode_number(&mut patch, (3 << 2) | 3);
+        encode_number(&mut patch, 4 << 1);
+        //Source copy of GH from offset 6
+        encode_number(&mut patch, (1 << 2) | 2);
+        encode_number(&mut patch, 6 << 1);
+        let patch = finish_patch(patch, &source, &target);
+        assert_eq!(apply_patch(&source, &patch), Ok(target));
+        assert_eq!(apply_patch(b"ABCDEFG", &patch), Err(PatchError::WrongSourceSize { expected: 8, actual: 7 }));
+    }
+
+    //Builds a BPS patch with valid checksums around the given commands
+    fn bps_patch(source: &[u8], target_size: usize, commands: &[usize]) -> Vec<u8>
+    {
+        let mut patch = b"BPS1".to_vec();
+        encode_number(&mut patch, source.len());
+        encode_number(&mut patch, target_size);
+        encode_number(&mut patch, 0);
+        for command in commands.iter()
+        {
+            encode_number(&mut patch, *command);
+        }
+        return finish_patch(patch, source, &[]);
+    }
+
+    #[test]
+    fn hostile_patch_test()
+    {
+        let source = b"ABCDEFGH".to_vec();
+        //Claims a huge target without allocating it
+        assert_eq!(apply_patch(&source, &bps_patch(&source, usize::max_value() >> 8, &[])), Err(PatchError::OutOfBounds));
+        //Target copy writing past the declared target size
+        assert_eq!(apply_patch(&source, &bps_patch(&source, 4, &[(0 << 2) | 0, (100 << 2) | 3, 0])), Err(PatchError::OutOfBounds));
+        //Source copy with an offset that would overflow
+        assert_eq!(apply_patch(&source, &bps_patch(&source, 8, &[(0 << 2) | 2, usize::max_value() - 1])), Err(PatchError::OutOfBounds));
+        assert_eq!(apply_patch(&source, &bps_patch(&source, 8, &[(0 << 2) | 2, 1 << 1, (7 << 2) | 2, 0])), Err(PatchError::OutOfBounds));
+
+        let mut patch = b"UPS1".to_vec();
+        encode_number(&mut patch, source.len());
+        encode_number(&mut patch, usize::max_value() >> 8);
+        let patch = finish_patch(patch, &source, &[]);
+        assert_eq!(apply_patch(&source, &patch), Err(PatchError::OutOfBounds));
+    }
+
+    #[test]
+    fn truncated_patch_test()
+    {
+        let source = b"ABCDEFGH".to_vec();
+        //Target read of four bytes with only two left in the patch
+        let mut patch = b"BPS1".to_vec();
+        encode_number(&mut patch, source.len());
+        encode_number(&mut patch, 4);
+        encode_number(&mut patch, 0);
+        encode_number(&mut patch, (3 << 2) | 1);
+        patch.extend_from_slice(b"xy");
+        let patch = finish_patch(patch, &source, &[]);
+        assert_eq!(apply_patch(&source, &patch), Err(PatchError::Truncated));
+        assert_eq!(apply_patch(&source, b"BPS1\x88"), Err(PatchError::Truncated));
+        assert_eq!(apply_patch(&source, b"UPS1\x88\x88"), Err(PatchError::Truncated));
+    }
+}
@@ -1,63 +1,345 @@
 #![allow(dead_code)]
 
-extern crate csv;
-extern crate hex;
+extern crate rustboy;
 extern crate piston;
 extern crate graphics;
 //extern crate glutin_window;
 extern crate opengl_graphics;
 extern crate image;
 extern crate sdl2_window;
+extern crate sdl2;
 //extern crate piston_window;
 
-mod cpu;
-mod gpu;
-mod system;
-mod mmu;
-mod timer;
-
 // use piston_window::*;
 // use piston_window::RenderEvent;
 // use piston_window::ReleaseEvent;
 // use piston_window::PressEvent;
-use image::ImageBuffer;
-use image::{RgbaImage, Rgba};
+use image::RgbaImage;
 use opengl_graphics::Texture;
 use piston::event_loop::*;
 use piston::input::*;
 use piston::window::WindowSettings;
 //use glutin_window::GlutinWindow as Window;
 use sdl2_window::Sdl2Window as Window;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 use opengl_graphics::{ GlGraphics, OpenGL };
 use std::env;
-use cpu::cpu::*;
-use gpu::gpu::*;
-use gpu::gpu_registers::*;
-use mmu::*;
-use timer::*;
-use system::*;
-use self::hex::FromHex;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use rustboy::GameBoy;
+use rustboy::model::Model;
+use rustboy::apu::playback::{SampleRingBuffer, RateControl};
+use rustboy::link::LinkCable;
+use rustboy::header::CartridgeHeader;
+use rustboy::loader::{LoadError, read_rom_file};
+use rustboy::system::PlayerInput;
+
+static USAGE: &str = "Usage: RustBoy <rom> [--link-host <port> | --link-connect <[address:]port>] [--patch <file>] [--boot-rom <file>] [--model <dmg|mgb|sgb|cgb>] [--pixel-fifo]\n       RustBoy rom-info <rom>";
+
+pub struct FrontendOptions
+{
+    pub rom_file: String,
+    pub link_host: Option<u16>,
+    pub link_connect: Option<String>,
+    pub patch_file: Option<String>,
+    pub boot_rom_file: Option<String>,
+    pub model: Model,
+    pub pixel_fifo: bool,
+}
+
+impl FrontendOptions
+{
+    pub fn parse(args: &[String]) -> Result<FrontendOptions, String>
+    {
+        let mut options = FrontendOptions
+        {
+            rom_file: String::new(),
+            link_host: None,
+            link_connect: None,
+            patch_file: None,
+            boot_rom_file: None,
+            model: Model::DMG,
+            pixel_fifo: false,
+        };
+
+        let mut index = 0;
+        while index < args.len()
+        {
+            let arg = &args[index];
+            match arg.as_ref()
+            {
+                "--pixel-fifo" =>
+                {
+                    options.pixel_fifo = true;
+                    index += 1;
+                },
+                "--link-host" | "--link-connect" | "--patch" | "--boot-rom" | "--model" =>
+                {
+                    if index + 1 >= args.len()
+                    {
+                        return Err(format!("Missing value for {}", arg));
+                    }
+                    let value = &args[index + 1];
+                    if arg == "--link-host"
+                    {
+                        options.link_host = Some(value.parse::<u16>().map_err(|_| format!("Invalid port: {}", value))?);
+                    }
+                    else if arg == "--link-connect"
+                    {
+                        options.link_connect = Some(value.clone());
+                    }
+                    else if arg == "--patch"
+                    {
+                        options.patch_file = Some(value.clone());
+                    }
+                    else if arg == "--boot-rom"
+                    {
+                        options.boot_rom_file = Some(value.clone());
+                    }
+                    else
+                    {
+                        options.model = Model::parse(value).ok_or(format!("Unknown model: {}", value))?;
+                    }
+                    index += 2;
+                },
+                _ =>
+                {
+                    if !options.rom_file.is_empty()
+                    {
+                        return Err(format!("Unexpected argument {}", arg));
+                    }
+                    options.rom_file = arg.clone();
+                    index += 1;
+                },
+            }
+        }
+
+        if options.rom_file.is_empty()
+        {
+            return Err(String::from("No ROM file given"));
+        }
+        if options.link_host.is_some() && options.link_connect.is_some()
+        {
+            return Err(String::from("Only one of --link-host and --link-connect can be given"));
+        }
+        return Ok(options);
+    }
+}
+
+fn open_link_cable(options: &FrontendOptions) -> Option<LinkCable>
+{
+    let link = if let Some(port) = options.link_host
+    {
+        println!("Waiting for link cable connection on port {}", port);
+        LinkCable::host(port)
+    }
+    else if let Some(ref address) = options.link_connect
+    {
+        LinkCable::connect(address)
+    }
+    else
+    {
+        return None;
+    };
+
+    match link
+    {
+        Ok(link) => return Some(link),
+        Err(error) =>
+        {
+            eprintln!("Could not open link cable: {}", error);
+            process::exit(1);
+        },
+    }
+}
+
+//Prints the decoded cartridge header, returns false if the file is unreadable or a checksum is off
+fn print_rom_info(file_name: &str) -> bool
+{
+    let rom = match read_rom_file(file_name)
+    {
+        Ok(rom) => rom,
+        Err(error) =>
+        {
+            eprintln!("{}", error);
+            return false;
+        },
+    };
+    let header = match CartridgeHeader::parse(&rom)
+    {
+        Some(header) => header,
+        None =>
+        {
+            eprintln!("{}", LoadError::Truncated(rom.len()));
+            return false;
+        },
+    };
+    println!("{}", header);
+    let problems = header.checksum_problems();
+    for problem in problems.iter()
+    {
+        println!("WARNING: {}", problem);
+    }
+    return problems.is_empty();
+}
+
+//Runs emulated frames off the wall clock so speed does not depend on how often piston renders
+pub struct FramePacer
+{
+    pub vertical_sync: f64,
+    pub frames_run: u64,
+}
+
+//Frames to run at most per render before giving up on catching up
+static MAX_CATCH_UP_FRAMES: u64 = 3;
+
+impl FramePacer
+{
+    pub fn new(vertical_sync: f64) -> FramePacer
+    {
+        return FramePacer
+        {
+            vertical_sync: vertical_sync,
+            frames_run: 0,
+        }
+    }
+
+    pub fn frames_due(&mut self, elapsed_seconds: f64) -> u64
+    {
+        let target_frames = (elapsed_seconds * self.vertical_sync) as u64;
+        if target_frames <= self.frames_run
+        {
+            return 0;
+        }
+        let mut frames = target_frames - self.frames_run;
+        if frames > MAX_CATCH_UP_FRAMES
+        {
+            frames = MAX_CATCH_UP_FRAMES;
+            self.frames_run = target_frames - frames;
+        }
+        self.frames_run += frames;
+        return frames;
+    }
+}
 
-static MAX_SPRITE: u8 = 40;
+static AUDIO_SAMPLE_RATE: i32 = 44100;
+//Roughly 90ms of interleaved stereo samples
+static AUDIO_BUFFER_SAMPLES: usize = 8192;
 
+struct AudioOutput
+{
+    buffer: Arc<Mutex<SampleRingBuffer>>,
+}
+
+impl AudioCallback for AudioOutput
+{
+    type Channel = f32;
+
+    fn callback(&mut self, output: &mut [f32])
+    {
+        match self.buffer.lock()
+        {
+            Ok(mut buffer) => { buffer.pop(output); },
+            Err(_) => for sample in output.iter_mut() { *sample = 0.0; },
+        }
+    }
+}
+
+fn open_audio(window: &Window, buffer: &Arc<Mutex<SampleRingBuffer>>) -> Option<(AudioDevice<AudioOutput>, RateControl)>
+{
+    let audio_subsystem = match window.sdl_context.audio()
+    {
+        Ok(audio_subsystem) => audio_subsystem,
+        Err(error) =>
+        {
+            eprintln!("Could not open audio, running without sound: {}", error);
+            return None;
+        },
+    };
+    let desired_spec = AudioSpecDesired
+    {
+        freq: Some(AUDIO_SAMPLE_RATE),
+        channels: Some(2),
+        samples: Some(1024),
+    };
+    let mut host_rate = AUDIO_SAMPLE_RATE as f64;
+    let device = audio_subsystem.open_playback(None, &desired_spec, |spec|
+    {
+        host_rate = spec.freq as f64;
+        AudioOutput
+        {
+            buffer: buffer.clone(),
+        }
+    });
+    match device
+    {
+        Ok(device) =>
+        {
+            device.resume();
+            return Some((device, RateControl::new(host_rate)));
+        },
+        Err(error) =>
+        {
+            eprintln!("Could not open audio, running without sound: {}", error);
+            return None;
+        },
+    }
+}
 
 fn main()
  {
     //Initialize Emulator
-    let args: Vec<String> = env::args().collect();
-    let emulator_type: String = String::from("CLASSIC");
-    let file_name: &String = &args[1];
-    let mut system_data : SystemData = get_system_data(&emulator_type);
-    system_data.mmu.initialize_cartridge(file_name);
-    let mut registers: Registers = Registers::new();
-    let mut gpu_registers: GPU_Registers = GPU_Registers::new();
-    init_emulator_state(&mut system_data, &mut registers);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map_or(false, |arg| arg == "rom-info")
+    {
+        if args.len() != 2
+        {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+        let valid = print_rom_info(&args[1]);
+        process::exit(if valid { 0 } else { 1 });
+    }
+    let options = match FrontendOptions::parse(&args)
+    {
+        Ok(options) => options,
+        Err(message) =>
+        {
+            eprintln!("{}", message);
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        },
+    };
+    let mut gameboy = GameBoy::with_model(options.model);
+    if options.pixel_fifo
+    {
+        gameboy.use_pixel_fifo();
+    }
+    if let Some(ref boot_rom_file) = options.boot_rom_file
+    {
+        if let Err(error) = gameboy.set_boot_rom(boot_rom_file)
+        {
+            eprintln!("Could not load boot ROM {}: {}", boot_rom_file, error);
+            process::exit(1);
+        }
+    }
+    if let Err(error) = gameboy.load_rom_with_patch(&options.rom_file, options.patch_file.as_ref().map(|file_name| file_name.as_str()))
+    {
+        eprintln!("Could not load {}: {}", options.rom_file, error);
+        process::exit(1);
+    }
+    if let Some(ref patch_file) = gameboy.system_data.mmu.patch_file
+    {
+        println!("Applied patch {}", patch_file);
+    }
+    gameboy.system_data.serial.link = open_link_cable(&options);
 
     //Initialize Screen
     let opengl = OpenGL::V3_2;
-    
+
     let scale_factor = 2.0;
-    let mut window: Window = piston::window::WindowSettings::new("RustBoy", [(system_data.width as f64 * scale_factor) as u32, (system_data.height as f64 * scale_factor) as u32])
+    let mut window: Window = piston::window::WindowSettings::new("RustBoy", [(gameboy.system_data.width as f64 * scale_factor) as u32, (gameboy.system_data.height as f64 * scale_factor) as u32])
                                         .opengl(opengl)
                                         .exit_on_esc(true)
                                         .build()
@@ -68,21 +350,24 @@ fn main()
         gl: GlGraphics::new(OpenGL::V3_2),
     };
 
-    
-    
+
+
+    let audio_buffer = Arc::new(Mutex::new(SampleRingBuffer::new(AUDIO_BUFFER_SAMPLES)));
+    let audio = open_audio(&window, &audio_buffer);
+    if let Some((_, ref rate_control)) = audio
+    {
+        gameboy.adjust_audio_sample_rate(rate_control.host_rate);
+    }
+
     let mut events = Events::new(EventSettings::new());
 
-    events.set_max_fps(65);
-    let mut background_tile_map: TileMap = TileMap::new();
-    let mut window_tile_map: TileMap = TileMap::new();
-    let mut oam_tile_map: TileMap = TileMap::new();
-    let mut oam_table = OAM_Table::new();
-  
-    //Operation loop
-    let mut emulator_loop = true;
-    gpu_registers.v_blank_draw_flag = false;
+    //Render often enough that the pacer never has to skip a frame
+    events.set_max_fps(120);
 
-    let mut space_flag = false;
+    //Operation loop
+    let mut input = PlayerInput::new();
+    let mut pacer = FramePacer::new(gameboy.system_data.vertical_sync);
+    let start_time = Instant::now();
 
     while let Some(e) = events.next(&mut window)
     {
@@ -90,67 +375,54 @@ fn main()
         {
             match key
             {
-                Key::A => system_data.input.left = true,
-                Key::D => system_data.input.right = true,
-                Key::W => system_data.input.up = true,
-                Key::S => system_data.input.down = true,
-                Key::NumPad1 => system_data.input.a_button = true,
-                Key::NumPad2 => system_data.input.b_button = true,
-                Key::Return => system_data.input.start = true,
-                Key::Space => system_data.input.select = true,
+                Key::A => input.left = true,
+                Key::D => input.right = true,
+                Key::W => input.up = true,
+                Key::S => input.down = true,
+                Key::NumPad1 => input.a_button = true,
+                Key::NumPad2 => input.b_button = true,
+                Key::Return => input.start = true,
+                Key::Space => input.select = true,
                 _ => (),
             }
-        } 
-        
+        }
+
         if let Some(Button::Keyboard(key)) = e.release_args()
         {
             match key
             {
-                Key::A => system_data.input.left = false,
-                Key::D => system_data.input.right = false,
-                Key::W => system_data.input.up = false,
-                Key::S => system_data.input.down = false,
-                Key::NumPad1 => system_data.input.a_button = false,
-                Key::NumPad2 => system_data.input.b_button = false,
-                Key::Return => system_data.input.start = false,
-                Key::Space => system_data.input.select = false,
+                Key::A => input.left = false,
+                Key::D => input.right = false,
+                Key::W => input.up = false,
+                Key::S => input.down = false,
+                Key::NumPad1 => input.a_button = false,
+                Key::NumPad2 => input.b_button = false,
+                Key::Return => input.start = false,
+                Key::Space => input.select = false,
                 _ => (),
             }
-        }    
+        }
 
 
         if let Some(r) = e.render_args(){
-                while !gpu_registers.v_blank_draw_flag
+                gameboy.set_input(input);
+                let elapsed = start_time.elapsed();
+                let elapsed_seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0;
+                for _ in 0..pacer.frames_due(elapsed_seconds)
                 {
-                    let joypad_input = system_data.input.update_input(&system_data);
-                    if (joypad_input & 0x0F) != 0x0F
-                    {
-                        let current_if = system_data.mmu.get_from_memory(0xFF0F, false);
-                        system_data.mmu.set_to_memory(0xFF0F, current_if | 0x10, false);
-                    }
-                    system_data.mmu.set_to_memory(0xFF00, joypad_input, false);
-                    let opcode = system_data.mmu.get_from_memory(registers.program_counter as usize, false);
-                    let address = registers.program_counter;
-                    cpu_continue(&mut system_data, &mut registers);
-                    update_gpu(&mut system_data, &mut registers, &mut gpu_registers);
-                    system_data.timer_tick();
-                }   
-                gpu_registers.v_blank_draw_flag = false;
-                background_tile_map.populate_tile_map(&mut system_data, gpu_registers.lcdc_register.tile_data, gpu_registers.lcdc_register.background_display_select);  
-                window_tile_map.populate_tile_map(&mut system_data, gpu_registers.lcdc_register.tile_data, gpu_registers.lcdc_register.window_display_select);
-                oam_tile_map.populate_tile_map(&mut system_data, true, true);
-                oam_table.populate_oam_table(&system_data);
-                let image: RgbaImage = create_background_img(&background_tile_map, &window_tile_map, &gpu_registers, &system_data, &oam_table, &oam_tile_map);
-                app.render(&image, &r, scale_factor);
-                // break;
+                    gameboy.run_frame();
+                }
+                if let Some((_, ref rate_control)) = audio
+                {
+                    let samples = gameboy.take_audio_samples();
+                    let mut buffer = audio_buffer.lock().unwrap();
+                    buffer.push(&samples);
+                    gameboy.adjust_audio_sample_rate(rate_control.sample_rate(buffer.len(), buffer.capacity()));
+                }
+                app.render(gameboy.framebuffer(), &r, scale_factor);
         }
     }
-        // if system_data.cycles == 0  //|| registers.program_counter == 0x6d 
-        // {
-        //     emulator_loop = false;
-        //     println!("Location: {:04X}\tOpcode: 0x{:02X}  {:08b}", address, opcode, opcode);
-        // }
-    //Cleanup?
+    gameboy.save_battery();
 }
 
 
@@ -166,8 +438,8 @@ impl App
             use graphics::*;
             let BLANK: types::Color = color::hex("9CBD0F");
             let tile = Texture::from_image(img, &opengl_graphics::TextureSettings::new());
-            
-            self.gl.draw(args.viewport(), |c, gl| 
+
+            self.gl.draw(args.viewport(), |c, gl|
             {
                 clear(color::BLACK, gl);
                 let transform2 = graphics::Transformed::trans(c.transform, 0.0, 0.0);
@@ -178,29 +450,56 @@ impl App
     }
 }
 
-fn init_emulator_state(system_data: &mut SystemData, registers: &mut Registers)
+#[cfg(test)]
+mod frontend_tests
 {
-    registers.program_counter = 0x100;
+    use FrontendOptions;
+    use rustboy::model::Model;
+    use FramePacer;
 
-    let states: Vec<u8> = vec![0x00, 0x00, 0x00, 0x80, 0xBF, 0xF3, 0xBF, 0x3F, 
-                               0x00, 0xBF, 0x7F, 0xFF, 0x9F, 0xBF, 0xFF, 0x00,
-                               0x00, 0xBF, 0x77, 0xF3, 0xF1, 0x91, 0x00, 0x00, 
-                               0x00, 0xFC, 0xFF, 0xFF, 0x00, 0x00, 0x00];
-
-    let mem_locations: Vec<usize> = vec![0xFF05, 0xFF06, 0xFF07, 0xFF10, 0xFF11, 0xFF12, 
-                                       0xFF14, 0xFF16, 0xFF17, 0xFF19, 0xFF1A, 0xFF1B, 
-                                       0xFF1C, 0xFF1E, 0xFF20, 0xFF21, 0xFF22, 0xFF23, 
-                                       0xFF24, 0xFF25, 0xFF26, 0xFF40, 0xFF42, 0xFF43, 
-                                       0xFF45, 0xFF47, 0xFF48, 0xFF49, 0xFF4A, 0xFF4B, 0xFFFF];
+    fn to_args(args: &[&str]) -> Vec<String>
+    {
+        return args.iter().map(|arg| String::from(*arg)).collect();
+    }
 
-    for i in 0..states.len()
+    #[test]
+    fn parse_link_options_test()
     {
-        system_data.mmu.set_to_memory(mem_locations[i], states[i] ,false);
+        let options = FrontendOptions::parse(&to_args(&["game.gb"])).unwrap();
+        assert_eq!(options.rom_file, "game.gb");
+        assert_eq!(options.link_host, None);
+        assert_eq!(options.link_connect, None);
+
+        let options = FrontendOptions::parse(&to_args(&["game.gb", "--link-host", "5000"])).unwrap();
+        assert_eq!(options.link_host, Some(5000));
+
+        let options = FrontendOptions::parse(&to_args(&["--link-connect", "127.0.0.1:5000", "game.gb"])).unwrap();
+        assert_eq!(options.link_connect, Some(String::from("127.0.0.1:5000")));
+
+        let options = FrontendOptions::parse(&to_args(&["game.gb", "--patch", "translation.bps", "--boot-rom", "dmg_boot.bin"])).unwrap();
+        assert_eq!(options.patch_file, Some(String::from("translation.bps")));
+        assert_eq!(options.boot_rom_file, Some(String::from("dmg_boot.bin")));
+        assert_eq!(options.model, Model::DMG);
+
+        let options = FrontendOptions::parse(&to_args(&["--model", "cgb", "game.gb", "--pixel-fifo"])).unwrap();
+        assert_eq!(options.model, Model::CGB);
+        assert!(options.pixel_fifo);
+        assert!(FrontendOptions::parse(&to_args(&["game.gb", "--model", "gba"])).is_err());
+
+        assert!(FrontendOptions::parse(&to_args(&["game.gb", "--link-host", "port"])).is_err());
+        assert!(FrontendOptions::parse(&to_args(&["game.gb", "--link-host", "5000", "--link-connect", "5000"])).is_err());
+        assert!(FrontendOptions::parse(&to_args(&["--link-host", "5000"])).is_err());
     }
 
-    let register_states: Vec<u16> = vec![0x01B0, 0x0013, 0x00D8, 0x014D, 0xFFFE];
-    for i in 0..register_states.len()
+    #[test]
+    fn frame_pacer_test()
     {
-        registers.mapped_16_bit_register_setter(i as u8, register_states[i]);
+        let mut pacer = FramePacer::new(59.73);
+        assert_eq!(pacer.frames_due(0.01), 0);
+        assert_eq!(pacer.frames_due(0.02), 1);
+        assert_eq!(pacer.frames_due(0.025), 0);
+        assert_eq!(pacer.frames_due(1.0), 3);
+        assert_eq!(pacer.frames_run, 59);
+        assert_eq!(pacer.frames_due(1.02), 1);
     }
-}
\ No newline at end of file
+}
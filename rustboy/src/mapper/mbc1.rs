@@ -0,0 +1,148 @@
+use mapper::{Mapper, CartridgeMemory};
+
+pub struct Mbc1
+{
+    pub memory: CartridgeMemory,
+    pub rom_bank: u8,
+    pub ram_bank: u8,
+    pub ram_enable: bool,
+    pub banking_mode: u8,
+}
+
+impl Mbc1
+{
+    pub fn new(memory: CartridgeMemory) -> Mbc1
+    {
+        return Mbc1
+        {
+            memory: memory,
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enable: false,
+            banking_mode: 0,
+        }
+    }
+}
+
+impl Mapper for Mbc1
+{
+    fn read(&self, location: usize) -> u8
+    {
+        match location
+        {
+            0x4000...0x7FFF => return self.memory.read_rom(self.rom_bank as usize, location - 0x4000),
+            0xA000...0xBFFF =>
+            {
+                if self.ram_enable
+                {
+                    return self.memory.read_ram(self.ram_bank as usize, location - 0xA000);
+                }
+                return 0xFF;
+            },
+            _ => return 0xFF,
+        }
+    }
+
+    fn write(&mut self, location: usize, value: u8) -> bool
+    {
+        match location
+        {
+            0x0000...0x1FFF =>
+            {
+                if (value & 0x0F) == 0x0A
+                {
+                    self.ram_enable = true;
+                }
+                else {
+                    self.ram_enable = false;
+                }
+            },
+            0x2000...0x3FFF =>
+            {
+                let mut bank = value & 0x1F;
+                if bank == 0
+                {
+                    bank += 1;
+                }
+                self.rom_bank &= 0xE0;
+                self.rom_bank |= bank;
+            },
+            0x4000...0x5FFF =>
+            {
+                self.rom_bank &= 0x1F;
+                if self.banking_mode == 0{
+                    self.rom_bank |= (value & 0x03) << 5;
+                }
+                else
+                {
+                    self.ram_bank = value & 0x03;
+                }
+            },
+            0x6000...0x7FFF =>
+            {
+                self.banking_mode = value & 0x01;
+                if self.banking_mode == 0
+                {
+                    self.ram_bank = 0;
+                }
+                else
+                {
+                    self.rom_bank &= 0x1F;
+                }
+            },
+            0xA000...0xBFFF =>
+            {
+                if self.ram_enable
+                {
+                    self.memory.write_ram(self.ram_bank as usize, location - 0xA000, value);
+                }
+            }
+            _ => (),
+        }
+        return true;
+    }
+
+    fn memory(&self) -> &CartridgeMemory
+    {
+        return &self.memory;
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory
+    {
+        return &mut self.memory;
+    }
+}
+
+#[cfg(test)]
+mod mbc1_tests
+{
+    use mapper::*;
+    use mapper::mbc1::*;
+
+    #[test]
+    fn mbc1_test()
+    {
+        let mut mbc1 = Mbc1::new(CartridgeMemory::new(128, 0x8000));
+
+        let memory_locations: Vec<usize> = vec![0x2000, 0x3000, 0x3FFF, 0x4000];
+        let values: Vec<u8> = vec![0xFF, 0x00, 0xFF, 0x03];
+        let rom_bank_values: Vec<u8> = vec![0x1F, 0x01, 0x1F, 0x61];
+
+        for i in 0..memory_locations.len()
+        {
+            mbc1.rom_bank = 0x01;
+            mbc1.write(memory_locations[i], values[i]);
+            assert_eq!(mbc1.rom_bank, rom_bank_values[i]);
+        }
+
+        mbc1.write(0x0000, 0x0A);
+        mbc1.write(0x6000, 0x01);
+        assert_eq!(mbc1.rom_bank, 0x01);
+        mbc1.write(0x4000, 0x02);
+        mbc1.write(0xA010, 0x34);
+        assert_eq!(mbc1.memory.ram[0x4010], 0x34);
+        assert_eq!(mbc1.read(0xA010), 0x34);
+        mbc1.write(0x0000, 0x00);
+        assert_eq!(mbc1.read(0xA010), 0xFF);
+    }
+}
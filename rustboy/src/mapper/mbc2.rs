@@ -0,0 +1,121 @@
+use mapper::{Mapper, CartridgeMemory};
+
+//MBC2 has 512 half-byte cells of RAM built into the controller
+pub struct Mbc2
+{
+    pub memory: CartridgeMemory,
+    pub rom_bank: u8,
+    pub ram_enable: bool,
+}
+
+impl Mbc2
+{
+    pub fn new(mut memory: CartridgeMemory) -> Mbc2
+    {
+        memory.ram = vec![0; 0x200];
+        return Mbc2
+        {
+            memory: memory,
+            rom_bank: 1,
+            ram_enable: false,
+        }
+    }
+}
+
+impl Mapper for Mbc2
+{
+    fn read(&self, location: usize) -> u8
+    {
+        match location
+        {
+            0x4000...0x7FFF => return self.memory.read_rom(self.rom_bank as usize, location - 0x4000),
+            0xA000...0xBFFF =>
+            {
+                if self.ram_enable
+                {
+                    //Only the low nibble exists, the upper bits read as 1
+                    return self.memory.ram[location & 0x1FF] | 0xF0;
+                }
+                return 0xFF;
+            },
+            _ => return 0xFF,
+        }
+    }
+
+    fn write(&mut self, location: usize, value: u8) -> bool
+    {
+        match location
+        {
+            0x0000...0x3FFF =>
+            {
+                //Address bit 8 picks between RAM enable and ROM bank select
+                if (location & 0x0100) == 0
+                {
+                    self.ram_enable = (value & 0x0F) == 0x0A;
+                }
+                else
+                {
+                    let mut bank = value & 0x0F;
+                    if bank == 0
+                    {
+                        bank += 1;
+                    }
+                    self.rom_bank = bank;
+                }
+            },
+            0xA000...0xBFFF =>
+            {
+                //Echoed across the whole RAM area
+                if self.ram_enable
+                {
+                    self.memory.ram[location & 0x1FF] = value & 0x0F;
+                }
+            }
+            _ => (),
+        }
+        return true;
+    }
+
+    fn memory(&self) -> &CartridgeMemory
+    {
+        return &self.memory;
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory
+    {
+        return &mut self.memory;
+    }
+}
+
+#[cfg(test)]
+mod mbc2_tests
+{
+    use mapper::*;
+    use mapper::mbc2::*;
+
+    #[test]
+    fn mbc2_test()
+    {
+        let mut mbc2 = Mbc2::new(CartridgeMemory::new(16, 0));
+        mbc2.memory.rom_banks[0x0F][0x0000] = 0x77;
+
+        mbc2.write(0x2100, 0x0F);
+        assert_eq!(mbc2.rom_bank, 0x0F);
+        assert_eq!(mbc2.read(0x4000), 0x77);
+        mbc2.write(0x2100, 0x00);
+        assert_eq!(mbc2.rom_bank, 0x01);
+        mbc2.write(0x2000, 0x05);
+        assert_eq!(mbc2.rom_bank, 0x01);
+        assert!(!mbc2.ram_enable);
+        assert_eq!(mbc2.read(0xA000), 0xFF);
+
+        mbc2.write(0x0000, 0x0A);
+        assert!(mbc2.ram_enable);
+        mbc2.write(0xA005, 0xAB);
+        assert_eq!(mbc2.memory.ram[0x005], 0x0B);
+        assert_eq!(mbc2.read(0xA005), 0xFB);
+        assert_eq!(mbc2.read(0xA205), 0xFB);
+        assert_eq!(mbc2.read(0xBE05), 0xFB);
+        assert_eq!(mbc2.save().len(), 0x200);
+    }
+}
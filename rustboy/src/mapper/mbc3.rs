@@ -0,0 +1,176 @@
+use mapper::{Mapper, CartridgeMemory};
+use rtc::{RealTimeClock, RTC_FOOTER_SIZE, unix_time};
+
+pub struct Mbc3
+{
+    pub memory: CartridgeMemory,
+    pub rom_bank: u8,
+    pub ram_rtc_bank: u8,
+    pub ram_enable: bool,
+    pub has_rtc: bool,
+    pub rtc: RealTimeClock,
+}
+
+impl Mbc3
+{
+    pub fn new(memory: CartridgeMemory, has_rtc: bool) -> Mbc3
+    {
+        return Mbc3
+        {
+            memory: memory,
+            rom_bank: 1,
+            ram_rtc_bank: 0,
+            ram_enable: false,
+            has_rtc: has_rtc,
+            rtc: RealTimeClock::new(),
+        }
+    }
+}
+
+impl Mapper for Mbc3
+{
+    fn read(&self, location: usize) -> u8
+    {
+        match location
+        {
+            0x4000...0x7FFF => return self.memory.read_rom(self.rom_bank as usize, location - 0x4000),
+            0xA000...0xBFFF =>
+            {
+                if !self.ram_enable
+                {
+                    return 0xFF;
+                }
+                if self.ram_rtc_bank >= 0x04
+                {
+                    return self.rtc.read_register(self.ram_rtc_bank);
+                }
+                return self.memory.read_ram(self.ram_rtc_bank as usize, location - 0xA000);
+            },
+            _ => return 0xFF,
+        }
+    }
+
+    fn write(&mut self, location: usize, value: u8) -> bool
+    {
+        match location
+        {
+            0x0000...0x1FFF => self.ram_enable = (value & 0x0F) == 0x0A,
+            0x2000...0x3FFF =>
+            {
+                let mut bank = value & 0x7F;
+                if bank == 0
+                {
+                    bank += 1;
+                }
+                self.rom_bank = bank;
+            },
+            0x4000...0x5FFF => self.ram_rtc_bank = value & 0x0F,
+            0x6000...0x7FFF => self.rtc.write_latch(value),
+            0xA000...0xBFFF =>
+            {
+                if self.ram_enable && self.ram_rtc_bank < 0x04
+                {
+                    self.memory.write_ram(self.ram_rtc_bank as usize, location - 0xA000, value);
+                }
+                else if self.ram_enable && self.ram_rtc_bank >= 0x08
+                {
+                    self.rtc.write_register(self.ram_rtc_bank, value);
+                }
+            }
+            _ => (),
+        }
+        return true;
+    }
+
+    fn memory(&self) -> &CartridgeMemory
+    {
+        return &self.memory;
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory
+    {
+        return &mut self.memory;
+    }
+
+    //Cartridges with a clock append the RTC footer after RAM
+    fn save(&self) -> Vec<u8>
+    {
+        let mut data = self.memory.ram.clone();
+        if self.has_rtc
+        {
+            data.extend(self.rtc.to_footer(unix_time()));
+        }
+        return data;
+    }
+
+    fn load(&mut self, data: &[u8])
+    {
+        self.memory.load_ram(data);
+        let footer_start = self.memory.ram.len();
+        if self.has_rtc && data.len() >= footer_start + RTC_FOOTER_SIZE
+        {
+            self.rtc.load_footer(&data[footer_start..footer_start + RTC_FOOTER_SIZE], unix_time());
+        }
+    }
+
+    fn tick(&mut self, cycles: u8)
+    {
+        if self.has_rtc
+        {
+            self.rtc.tick(cycles);
+        }
+    }
+}
+
+#[cfg(test)]
+mod mbc3_tests
+{
+    use mapper::*;
+    use mapper::mbc3::*;
+
+    #[test]
+    fn rtc_register_test()
+    {
+        let mut mbc3 = Mbc3::new(CartridgeMemory::new(4, 0x8000), true);
+        mbc3.write(0x0000, 0x0A);
+        mbc3.write(0x4000, 0x08);
+        mbc3.write(0xA000, 42);
+        mbc3.write(0x4000, 0x0C);
+        mbc3.write(0xA000, 0x41);
+        assert_eq!(mbc3.read(0xA000), 0x00);
+        mbc3.write(0x6000, 0x00);
+        mbc3.write(0x6000, 0x01);
+        assert_eq!(mbc3.read(0xA000), 0x41);
+        mbc3.write(0x4000, 0x08);
+        assert_eq!(mbc3.read(0xA000), 42);
+        assert_eq!(mbc3.rtc.days, 0x100);
+        assert!(mbc3.rtc.halt);
+    }
+
+    #[test]
+    fn ram_bank_test()
+    {
+        let mut mbc3 = Mbc3::new(CartridgeMemory::new(4, 0x8000), false);
+        mbc3.write(0x0000, 0x0A);
+        mbc3.write(0x4000, 0x03);
+        mbc3.write(0xA001, 0x99);
+        assert_eq!(mbc3.memory.ram[0x6001], 0x99);
+        assert_eq!(mbc3.read(0xA001), 0x99);
+        assert_eq!(mbc3.save().len(), 0x8000);
+    }
+
+    #[test]
+    fn save_footer_test()
+    {
+        let mut mbc3 = Mbc3::new(CartridgeMemory::new(4, 0x2000), true);
+        mbc3.memory.ram[0x10] = 0x55;
+        mbc3.rtc.write_register(0x0A, 7);
+        let data = mbc3.save();
+        assert_eq!(data.len(), 0x2000 + RTC_FOOTER_SIZE);
+
+        let mut loaded = Mbc3::new(CartridgeMemory::new(4, 0x2000), true);
+        loaded.load(&data);
+        assert_eq!(loaded.memory.ram[0x10], 0x55);
+        assert_eq!(loaded.rtc.hours, 7);
+    }
+}
@@ -0,0 +1,152 @@
+use mapper::{Mapper, CartridgeMemory};
+
+pub struct Mbc5
+{
+    pub memory: CartridgeMemory,
+    pub rom_bank: u16,
+    pub ram_bank: u8,
+    pub ram_enable: bool,
+    pub has_rumble: bool,
+    pub rumble: bool,
+    pub rumble_event: Option<bool>,
+}
+
+impl Mbc5
+{
+    pub fn new(memory: CartridgeMemory, has_rumble: bool) -> Mbc5
+    {
+        return Mbc5
+        {
+            memory: memory,
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enable: false,
+            has_rumble: has_rumble,
+            rumble: false,
+            rumble_event: None,
+        }
+    }
+}
+
+impl Mapper for Mbc5
+{
+    fn read(&self, location: usize) -> u8
+    {
+        match location
+        {
+            0x4000...0x7FFF => return self.memory.read_rom(self.rom_bank as usize, location - 0x4000),
+            0xA000...0xBFFF =>
+            {
+                if self.ram_enable
+                {
+                    return self.memory.read_ram(self.ram_bank as usize, location - 0xA000);
+                }
+                return 0xFF;
+            },
+            _ => return 0xFF,
+        }
+    }
+
+    fn write(&mut self, location: usize, value: u8) -> bool
+    {
+        match location
+        {
+            0x0000...0x1FFF => self.ram_enable = value == 0x0A,
+            0x2000...0x2FFF =>
+            {
+                //Unlike MBC1, bank 0 can be mapped to 0x4000
+                self.rom_bank = (self.rom_bank & 0x100) | value as u16;
+            },
+            0x3000...0x3FFF =>
+            {
+                self.rom_bank = (self.rom_bank & 0xFF) | ((value as u16 & 0x01) << 8);
+            },
+            0x4000...0x5FFF =>
+            {
+                if self.has_rumble
+                {
+                    //Bit 3 drives the motor instead of selecting RAM
+                    self.ram_bank = value & 0x07;
+                    let rumble = (value & 0x08) == 0x08;
+                    if rumble != self.rumble
+                    {
+                        self.rumble = rumble;
+                        self.rumble_event = Some(rumble);
+                    }
+                }
+                else
+                {
+                    self.ram_bank = value & 0x0F;
+                }
+            },
+            0xA000...0xBFFF =>
+            {
+                if self.ram_enable
+                {
+                    self.memory.write_ram(self.ram_bank as usize, location - 0xA000, value);
+                }
+            }
+            _ => (),
+        }
+        return true;
+    }
+
+    fn memory(&self) -> &CartridgeMemory
+    {
+        return &self.memory;
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory
+    {
+        return &mut self.memory;
+    }
+
+    fn take_rumble_event(&mut self) -> Option<bool>
+    {
+        return self.rumble_event.take();
+    }
+}
+
+#[cfg(test)]
+mod mbc5_tests
+{
+    use mapper::*;
+    use mapper::mbc5::*;
+
+    #[test]
+    fn mbc5_bank_switch_test()
+    {
+        let mut mbc5 = Mbc5::new(CartridgeMemory::new(0x200, 0x20000), false);
+        mbc5.memory.rom_banks[0x000][0x0010] = 0x12;
+        mbc5.memory.rom_banks[0x1FF][0x0010] = 0x34;
+
+        mbc5.write(0x2000, 0x00);
+        assert_eq!(mbc5.rom_bank, 0x000);
+        assert_eq!(mbc5.read(0x4010), 0x12);
+        mbc5.write(0x2000, 0xFF);
+        mbc5.write(0x3000, 0x01);
+        assert_eq!(mbc5.rom_bank, 0x1FF);
+        assert_eq!(mbc5.read(0x4010), 0x34);
+
+        mbc5.write(0x0000, 0x0A);
+        mbc5.write(0x4000, 0x0F);
+        mbc5.write(0xA000, 0x56);
+        assert_eq!(mbc5.memory.ram[0x1E000], 0x56);
+        assert_eq!(mbc5.read(0xA000), 0x56);
+        assert!(!mbc5.rumble);
+    }
+
+    #[test]
+    fn mbc5_rumble_test()
+    {
+        let mut mbc5 = Mbc5::new(CartridgeMemory::new(2, 0x8000), true);
+        mbc5.write(0x4000, 0x0A);
+        assert_eq!(mbc5.ram_bank, 0x02);
+        assert!(mbc5.rumble);
+        assert_eq!(mbc5.take_rumble_event(), Some(true));
+        mbc5.write(0x4000, 0x0B);
+        assert_eq!(mbc5.take_rumble_event(), None);
+        mbc5.write(0x4000, 0x00);
+        assert_eq!(mbc5.take_rumble_event(), Some(false));
+    }
+}
@@ -0,0 +1,218 @@
+pub mod rom_only;
+pub mod mbc1;
+pub mod mbc2;
+pub mod mbc3;
+pub mod mbc5;
+
+use std::fmt;
+use mapper::rom_only::RomOnly;
+use mapper::mbc1::Mbc1;
+use mapper::mbc2::Mbc2;
+use mapper::mbc3::Mbc3;
+use mapper::mbc5::Mbc5;
+
+//Cartridge hardware that sits behind 0x0000-0x7FFF and 0xA000-0xBFFF
+pub trait Mapper
+{
+    //Reads from switchable ROM at 0x4000-0x7FFF and external RAM at 0xA000-0xBFFF
+    fn read(&self, location: usize) -> u8;
+    //Writes to 0x0000-0x7FFF drive the controller, writes to 0xA000-0xBFFF go to RAM.
+    //Returns false if the cartridge ignored the write and it should land in the memory map.
+    fn write(&mut self, location: usize, value: u8) -> bool;
+    fn memory(&self) -> &CartridgeMemory;
+    fn memory_mut(&mut self) -> &mut CartridgeMemory;
+
+    //Battery backed data, laid out the same way as other emulators' .sav files
+    fn save(&self) -> Vec<u8>
+    {
+        return self.memory().ram.clone();
+    }
+
+    fn load(&mut self, data: &[u8])
+    {
+        self.memory_mut().load_ram(data);
+    }
+
+    fn tick(&mut self, _cycles: u8)
+    {
+    }
+
+    fn take_rumble_event(&mut self) -> Option<bool>
+    {
+        return None;
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MapperError
+{
+    UnsupportedCartridgeType(u8),
+}
+
+impl fmt::Display for MapperError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            MapperError::UnsupportedCartridgeType(cartridge_type) => write!(f, "Unsupported cartridge type 0x{:02X}", cartridge_type),
+        }
+    }
+}
+
+//ROM split into 16KB banks and external RAM as one flat block
+pub struct CartridgeMemory
+{
+    pub rom_banks: Vec<Vec<u8>>,
+    pub ram: Vec<u8>,
+}
+
+impl CartridgeMemory
+{
+    pub fn new(rom_bank_count: usize, ram_byte_count: usize) -> CartridgeMemory
+    {
+        return CartridgeMemory
+        {
+            rom_banks: vec![vec![0; 0x4000]; rom_bank_count.max(2)],
+            ram: vec![0; ram_byte_count],
+        }
+    }
+
+    pub fn from_rom(rom: &[u8], rom_bank_count: usize, ram_byte_count: usize) -> CartridgeMemory
+    {
+        let mut memory = CartridgeMemory::new(rom_bank_count, ram_byte_count);
+        for i in 0..memory.rom_banks.len()
+        {
+            let start = (0x4000 * i).min(rom.len());
+            let end = (0x4000 * (i + 1)).min(rom.len());
+            memory.rom_banks[i][0..end - start].copy_from_slice(&rom[start..end]);
+        }
+        return memory;
+    }
+
+    //Bank numbers past the end of the ROM wrap around, like the unconnected address lines do
+    pub fn read_rom(&self, bank: usize, offset: usize) -> u8
+    {
+        return self.rom_banks[bank % self.rom_banks.len()][offset];
+    }
+
+    pub fn read_ram(&self, bank: usize, offset: usize) -> u8
+    {
+        match self.ram_index(bank, offset)
+        {
+            Some(index) => return self.ram[index],
+            None => return 0xFF,
+        }
+    }
+
+    pub fn write_ram(&mut self, bank: usize, offset: usize, value: u8)
+    {
+        if let Some(index) = self.ram_index(bank, offset)
+        {
+            self.ram[index] = value;
+        }
+    }
+
+    fn ram_index(&self, bank: usize, offset: usize) -> Option<usize>
+    {
+        if self.ram.len() < 0x2000
+        {
+            //2KB chips only fill the start of the window
+            if offset >= self.ram.len()
+            {
+                return None;
+            }
+            return Some(offset);
+        }
+        return Some((bank * 0x2000 + offset) % self.ram.len());
+    }
+
+    pub fn load_ram(&mut self, data: &[u8])
+    {
+        let byte_count = data.len().min(self.ram.len());
+        self.ram[0..byte_count].copy_from_slice(&data[0..byte_count]);
+    }
+}
+
+pub fn create_mapper(cartridge_type: u8, memory: CartridgeMemory) -> Result<Box<dyn Mapper>, MapperError>
+{
+    match cartridge_type
+    {
+        0x00 | 0x08 | 0x09 => return Ok(Box::new(RomOnly::new(memory))),
+        0x01...0x03 => return Ok(Box::new(Mbc1::new(memory))),
+        0x05 | 0x06 => return Ok(Box::new(Mbc2::new(memory))),
+        0x0F...0x13 => return Ok(Box::new(Mbc3::new(memory, cartridge_type == 0x0F || cartridge_type == 0x10))),
+        0x19...0x1E => return Ok(Box::new(Mbc5::new(memory, cartridge_type >= 0x1C))),
+        _ => return Err(MapperError::UnsupportedCartridgeType(cartridge_type)),
+    }
+}
+
+//Only covers the cartridge types create_mapper accepts
+pub fn has_battery(cartridge_type: u8) -> bool
+{
+    match cartridge_type
+    {
+        0x03 | 0x06 | 0x09 | 0x0F | 0x10 | 0x13 | 0x1B | 0x1E => return true,
+        _ => return false,
+    }
+}
+
+#[cfg(test)]
+mod mapper_tests
+{
+    use mapper::*;
+
+    #[test]
+    fn create_mapper_test()
+    {
+        assert!(create_mapper(0x00, CartridgeMemory::new(2, 0)).is_ok());
+        assert!(create_mapper(0x13, CartridgeMemory::new(2, 0)).is_ok());
+        assert!(create_mapper(0x1E, CartridgeMemory::new(2, 0)).is_ok());
+        match create_mapper(0x22, CartridgeMemory::new(2, 0))
+        {
+            Err(error) =>
+            {
+                assert_eq!(error, MapperError::UnsupportedCartridgeType(0x22));
+                assert_eq!(format!("{}", error), "Unsupported cartridge type 0x22");
+            },
+            Ok(_) => panic!("cartridge type 0x22 should not be supported"),
+        }
+    }
+
+    #[test]
+    fn has_battery_test()
+    {
+        for cartridge_type in 0..0x100
+        {
+            let cartridge_type = cartridge_type as u8;
+            if has_battery(cartridge_type)
+            {
+                assert!(create_mapper(cartridge_type, CartridgeMemory::new(2, 0)).is_ok());
+            }
+        }
+        assert!(has_battery(0x03));
+        assert!(!has_battery(0x01));
+    }
+
+    #[test]
+    fn cartridge_memory_test()
+    {
+        let rom: Vec<u8> = (0..0xC000).map(|i| (i / 0x4000) as u8).collect();
+        let memory = CartridgeMemory::from_rom(&rom, 4, 0x800);
+        assert_eq!(memory.rom_banks.len(), 4);
+        assert_eq!(memory.read_rom(2, 0x1234), 2);
+        assert_eq!(memory.read_rom(3, 0x0000), 0);
+        assert_eq!(memory.read_rom(5, 0x0000), 1);
+
+        let mut memory = memory;
+        memory.write_ram(0, 0x7FF, 0x12);
+        memory.write_ram(0, 0x800, 0x34);
+        assert_eq!(memory.read_ram(0, 0x7FF), 0x12);
+        assert_eq!(memory.read_ram(0, 0x800), 0xFF);
+        assert_eq!(memory.read_ram(3, 0x7FF), 0x12);
+
+        let mut memory = CartridgeMemory::new(2, 0x8000);
+        memory.write_ram(5, 0x10, 0x56);
+        assert_eq!(memory.ram[0x2010], 0x56);
+    }
+}
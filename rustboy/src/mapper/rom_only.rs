@@ -0,0 +1,51 @@
+use mapper::{Mapper, CartridgeMemory};
+
+//32KB cartridges with no controller, optionally with RAM wired straight to 0xA000
+pub struct RomOnly
+{
+    pub memory: CartridgeMemory,
+}
+
+impl RomOnly
+{
+    pub fn new(memory: CartridgeMemory) -> RomOnly
+    {
+        return RomOnly
+        {
+            memory: memory,
+        }
+    }
+}
+
+impl Mapper for RomOnly
+{
+    fn read(&self, location: usize) -> u8
+    {
+        match location
+        {
+            0x4000...0x7FFF => return self.memory.read_rom(1, location - 0x4000),
+            0xA000...0xBFFF => return self.memory.read_ram(0, location - 0xA000),
+            _ => return 0xFF,
+        }
+    }
+
+    //There is no controller to drive, so ROM writes are dropped, as are RAM writes with no chip fitted
+    fn write(&mut self, location: usize, value: u8) -> bool
+    {
+        if location >= 0xA000 && location < 0xC000
+        {
+            self.memory.write_ram(0, location - 0xA000, value);
+        }
+        return true;
+    }
+
+    fn memory(&self) -> &CartridgeMemory
+    {
+        return &self.memory;
+    }
+
+    fn memory_mut(&mut self) -> &mut CartridgeMemory
+    {
+        return &mut self.memory;
+    }
+}
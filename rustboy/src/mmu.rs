@@ -1,24 +1,34 @@
 use std::fs::File;
 use std::io::prelude::*;
 use std::io;
+use std::path::Path;
+use apu::apu::SOUND_READ_MASKS;
+use mapper::{Mapper, CartridgeMemory, create_mapper, has_battery};
+use loader::{LoadError, load_rom_data, sibling_file_name};
+use header::CartridgeHeader;
 
 pub struct MMU
 {
     pub mem_map: Vec<u8>,
-    pub memory_banks: Vec<Vec<u8>>,
-    pub ram_banks: Vec<Vec<u8>>,
+    pub mapper: Box<dyn Mapper>,
+    pub header: Option<CartridgeHeader>,
+    //Mapped over 0x0000-0x00FF until the first write to 0xFF50
+    pub boot_rom: Option<Vec<u8>>,
     pub cartridge_type: u8,
-    pub rom_size: u8,
+    pub rom_size: u16,
     pub ram_size: u8,
-    pub rom_bank: u8,
-    pub ram_bank: u8,
-    pub ram_enable: bool,
-    pub banking_mode: u8,
     pub div_reset: bool,
-    pub rtc_enable: bool,
-    pub ram_rtc_bank: u8,
+    pub sound_writes: Vec<(usize, u8)>,
+    pub save_file: Option<String>,
+    //Patch applied by the last initialize_cartridge
+    pub patch_file: Option<String>,
+    pub ram_dirty: bool,
+    pub ram_idle_cycles: u32,
 }
 
+//Battery RAM is flushed once writes have stopped for about a second
+pub static SAVE_DELAY_CYCLES: u32 = 4194304;
+
 impl MMU
 {
     pub fn new() -> MMU
@@ -26,18 +36,18 @@ impl MMU
         return MMU
         {
             mem_map: vec![0; 0x10000],
-            memory_banks: vec![vec![0; 0x4000]; 0x100],
-            ram_banks: vec![vec![0; 0x2000]; 0x04],
+            mapper: create_mapper(0x00, CartridgeMemory::new(2, 0)).unwrap(),
+            header: None,
+            boot_rom: None,
             cartridge_type: 0,
             rom_size: 0x00,
             ram_size: 0x00,
-            rom_bank: 1,
-            ram_bank: 0,
-            ram_enable: false,
-            banking_mode: 0,
             div_reset: false,
-            rtc_enable: false,
-            ram_rtc_bank: 0,
+            sound_writes: Vec::new(),
+            save_file: None,
+            patch_file: None,
+            ram_dirty: false,
+            ram_idle_cycles: 0,
         }
     }
 
@@ -61,18 +71,33 @@ impl MMU
                 let previous_value = self.mem_map[location];
                 set_value = (previous_value & 0b11001111) | (set_value & 0b00110000);
             }
+            else if location == 0xFF41
+            {
+                //Mode and coincidence bits are read only
+                let previous_value = self.mem_map[location];
+                set_value = (previous_value & 0x07) | (set_value & 0x78);
+            }
             else if location == 0xFF04
             {
                 set_value = 0;
                 self.div_reset = true;
                 //self.mem_map[0xFF05] = 0;
             }
-            match self.cartridge_type
+            else if location >= 0xFF10 && location < 0xFF40
+            {
+                self.sound_writes.push((location, set_value));
+            }
+            else if location == 0xFF50
+            {
+                self.boot_rom = None;
+            }
+            else if location < 0x8000 || (location >= 0xA000 && location < 0xC000)
             {
-                0x00 => (),
-                0x01 | 0x02 | 0x03 => rom_flag = self.mbc1_parse(location, set_value),
-                0x13 => rom_flag = self.mbc3_parse(location, set_value),
-                _ => (),
+                rom_flag = self.mapper.write(location, set_value);
+                if rom_flag && location >= 0xA000
+                {
+                    self.mark_ram_dirty();
+                }
             }
         }
         if !rom_flag
@@ -80,7 +105,6 @@ impl MMU
             self.mem_map[location] = set_value;
         }
     }
-
     fn oam_dma_transfer(&mut self, value: u8)
     {
         //println!("DMA TRANSFER");
@@ -106,170 +130,77 @@ impl MMU
         {
             return self.mem_map[location_fixed] | 0b11111000;
         }
-        if location_fixed >= 0x4000 && location_fixed < 0x8000
+        else if location_fixed == 0xFF41
         {
-            return self.memory_banks[self.rom_bank as usize][location_fixed - 0x4000];
+            return self.mem_map[location_fixed] | 0x80;
         }
-        if location_fixed >= 0xA000 && location_fixed < 0xC000
+        else if location_fixed == 0xFF02
         {
-            if self.ram_enable && !self.rtc_enable
-            {
-                if (self.ram_size == 2 && location_fixed > 0xA7FF) || self.ram_size == 0
-                {
-                    return 0xFF;
-                }
-                else 
-                {
-                    return self.ram_banks[self.ram_bank as usize][location_fixed - 0xA000];
-                }
-                
-            }
-            if self.ram_enable && self.rtc_enable
-            {
-                if self.ram_rtc_bank >= 0x04
-                {
-                    //implement rtc
-                    return 0;
-                }
-                else if (self.ram_size == 2 && location_fixed > 0xA7FF) || self.ram_size == 0
-                {
-                    return 0xFF;
-                }
-                else 
-                {
-                    return self.ram_banks[self.ram_rtc_bank as usize][location_fixed - 0xA000];
-                }
-                
-            }
-            else 
+            return self.mem_map[location_fixed] | 0b01111110;
+        }
+        else if location_fixed >= 0xFF10 && location_fixed < 0xFF30
+        {
+            return self.mem_map[location_fixed] | SOUND_READ_MASKS[location_fixed - 0xFF10];
+        }
+        if location_fixed < 0x100
+        {
+            if let Some(ref boot_rom) = self.boot_rom
             {
-                return 0xFF;
+                return boot_rom[location_fixed];
             }
         }
+        if (location_fixed >= 0x4000 && location_fixed < 0x8000) || (location_fixed >= 0xA000 && location_fixed < 0xC000)
+        {
+            return self.mapper.read(location_fixed);
+        }
         return self.mem_map[location_fixed];
     }
 
-    fn mbc1_parse(&mut self, location: usize, value: u8) -> bool
+    fn mark_ram_dirty(&mut self)
     {
-        
-        match location
+        if self.save_file.is_some()
         {
-            0x0000...0x1FFF =>
-            {
-                if (value & 0x0F) == 0x0A
-                {
-                    self.ram_enable = true;
-                }
-                else {
-                    self.ram_enable = false;
-                }
-                return true;
-            },
-            0x2000...0x3FFF => 
-            {
-                let mut bank = value & 0x1F;
-                if bank == 0
-                {
-                    bank += 1;
-                }
-                self.rom_bank &= 0xE0;
-                self.rom_bank |= bank;
-                return true;
-            },
-            0x4000...0x5FFF =>
-            {
-                self.rom_bank &= 0x1F;
-                if self.banking_mode == 0{
-                    self.rom_bank |= ((value & 0x03) << 5);
-                }
-                else 
-                {
-                    self.ram_bank = (value & 0x03);  
-                }
-                return true;
-            },
-            0x6000...0x7FFF =>
-            {
-                self.banking_mode = (value & 0x01);
-                if self.banking_mode == 0
-                {
-                    self.ram_bank = 0;
-                }
-                else 
-                {
-                    self.rom_bank &= 0x1F;    
-                }
-                return true;
-            },
-            0xA000...0xBFFF =>
-            {
-                if self.ram_enable
-                {
-                    self.ram_banks[self.ram_bank as usize][location - 0xA000] = value;
-                }
-                return true;
-            }
-            _ => 
-            {
-                 return false;
-            },
+            self.ram_dirty = true;
+            self.ram_idle_cycles = 0;
         }
     }
 
-fn mbc3_parse(&mut self, location: usize, value: u8) -> bool
+    //Battery saves are a raw dump of external RAM, bank after bank,
+    //followed by the RTC footer for cartridges with a clock
+    pub fn load_battery_ram(&mut self, file_name: &str) -> io::Result<()>
     {
-        
-        match location
+        let mut buffer: Vec<u8> = Vec::new();
+        File::open(file_name)?.read_to_end(&mut buffer)?;
+        self.mapper.load(&buffer);
+        return Ok(());
+    }
+
+    pub fn save_battery_ram(&mut self) -> io::Result<()>
+    {
+        let file_name = match self.save_file
         {
-            0x0000...0x1FFF =>
-            {
-                if (value & 0x0F) == 0x0A
-                {
-                    self.ram_enable = true;
-                    self.rtc_enable = true;
-                }
-                else {
-                    self.ram_enable = false;
-                    self.rtc_enable = false;
-                }
-                return true;
-            },
-            0x2000...0x3FFF => 
-            {
-                let mut bank = value & 0x7F;
-                if bank == 0
-                {
-                    bank += 1;
-                }
-                self.rom_bank = bank;
-                return true;
-            },
-            0x4000...0x5FFF =>
-            {
-                self.ram_rtc_bank = value & 0x0F;
-                return true;
-            },
-            0x6000...0x7FFF =>
-            {
-                //IMPLEMENT LATCH
-                return true;
-            },
-            0xA000...0xBFFF =>
+            Some(ref file_name) => file_name.clone(),
+            None => return Ok(()),
+        };
+        File::create(file_name)?.write_all(&self.mapper.save())?;
+        self.ram_dirty = false;
+        return Ok(());
+    }
+
+    pub fn battery_tick(&mut self, cycles: u8)
+    {
+        if !self.ram_dirty
+        {
+            return;
+        }
+        self.ram_idle_cycles += cycles as u32;
+        if self.ram_idle_cycles >= SAVE_DELAY_CYCLES
+        {
+            if let Err(error) = self.save_battery_ram()
             {
-                if self.ram_enable && self.ram_rtc_bank < 0x04
-                {
-                    self.ram_banks[self.ram_bank as usize][location - 0xA000] = value;
-                }
-                else if self.rtc_enable && self.ram_rtc_bank >= 0x4000
-                {
-                    //Implement trc
-                }
-                return true;
+                println!("Could not write save file: {}", error);
+                self.ram_dirty = false;
             }
-            _ => 
-            {
-                 return false;
-            },
         }
     }
 
@@ -287,44 +218,53 @@ fn mbc3_parse(&mut self, location: usize, value: u8) -> bool
     return buffer;
     }
 
-    pub fn initialize_cartridge(&mut self, file_name: &str)
+    pub fn initialize_cartridge(&mut self, file_name: &str, patch_file: Option<&str>) -> Result<(), LoadError>
     {
-        let mut buffer: Vec<u8> = Vec::new();
-        let file = File::open(file_name);
-        if file.is_ok()
+        let rom_data = load_rom_data(file_name, patch_file)?;
+        let buffer = rom_data.data;
+        let header = match CartridgeHeader::parse(&buffer)
         {
-            if file.unwrap().read_to_end(&mut buffer).is_ok()
+            Some(header) => header,
+            None => return Err(LoadError::Truncated(buffer.len())),
+        };
+        let rom_size = self.parse_rom_size(header.rom_size_tag);
+        let ram_size = self.parse_ram_size(header.ram_size_tag);
+        let expected_size = (rom_size as usize).max(2) * 0x4000;
+        //Overdumped and padded ROMs are fine, only missing banks are an error
+        if buffer.len() < expected_size
+        {
+            return Err(LoadError::SizeMismatch { expected: expected_size, actual: buffer.len() });
+        }
+
+        let memory = CartridgeMemory::from_rom(&buffer, rom_size as usize, ram_size as usize * 0x400);
+        self.mapper = create_mapper(header.cartridge_type, memory)?;
+        self.cartridge_type = header.cartridge_type;
+        self.rom_size = rom_size;
+        self.ram_size = ram_size;
+        self.header = Some(header);
+        self.patch_file = rom_data.patch_file;
+        for i in 0..0x4000
+        {
+            self.mem_map[i] = self.mapper.memory().rom_banks[0][i];
+            self.mem_map[(i as usize) + 0x4000] = self.mapper.memory().rom_banks[1][i];
+        }
+
+        if has_battery(self.cartridge_type)
+        {
+            let save_file = save_file_name(file_name);
+            if Path::new(&save_file).exists()
             {
-                self.memory_banks[0] = buffer[0..0x4000].to_vec();
-                self.cartridge_type = self.memory_banks[0][0x0147];
-                let rom_tag = self.memory_banks[0][0x0148];
-                self.rom_size = self.parse_rom_size(rom_tag);
-                let ram_tag = self.memory_banks[0][0x0149];
-                self.ram_size = self.parse_ram_size(ram_tag);
-                if self.rom_size == 0
-                {
-                    self.memory_banks[1] = buffer[0x4000..0x8000].to_vec();
-                }
-                else
-                {
-                    for i in 1..self.rom_size
-                    {
-                        let start: usize = 0x4000 * (i as usize);
-                        let end: usize = 0x4000 * ((i as usize) + 1);
-                        self.memory_banks[i as usize] = buffer[start..end].to_vec();
-                    }
-                }
-                for i in 0..0x4000
+                if let Err(error) = self.load_battery_ram(&save_file)
                 {
-                    self.mem_map[i] = self.memory_banks[0][i];
-                    self.mem_map[(i as usize) + 0x4000] = self.memory_banks[1][i];
+                    println!("Could not read save file {}: {}", save_file, error);
                 }
-                
             }
+            self.save_file = Some(save_file);
         }
+        return Ok(());
     }
 
-    fn parse_rom_size(&mut self, rom_tag: u8) -> u8
+    fn parse_rom_size(&mut self, rom_tag: u8) -> u16
     {
         match rom_tag
         {
@@ -336,6 +276,7 @@ fn mbc3_parse(&mut self, location: usize, value: u8) -> bool
             0x05 => return 64,
             0x06 => return 128,
             0x07 => return 256,
+            0x08 => return 512,
             0x52 => return 72,
             0x53 => return 80,
             0x54 => return 96,
@@ -351,15 +292,25 @@ fn mbc3_parse(&mut self, location: usize, value: u8) -> bool
             0x01 => return 2,
             0x02 => return 8,
             0x03 => return 32,
+            0x04 => return 128,
+            0x05 => return 64,
             _ => return 0,
         }
     }
 }
 
+//game.gb is saved to game.sav, like other emulators
+pub fn save_file_name(rom_file_name: &str) -> String
+{
+    return sibling_file_name(rom_file_name, "sav");
+}
+
 #[cfg(test)]
 mod mmu_tests
 {
-    use MMU;
+    use mmu::*;
+    use std::env;
+    use std::fs;
     
     #[test]
     fn get_and_set_legal_memory_test() {
@@ -369,26 +320,6 @@ mod mmu_tests
         assert_eq!(mmu.get_from_memory(0x1234, false), 0xFF);
     }
 
-    //#[test]
-    fn mbc1_test() {
-        let mut mmu = MMU::new();
-        mmu.cartridge_type = 0x01;
-
-        let memory_locations: Vec<usize> = vec![0x00, 0x2000, 0x3000, 0x3FFF, 0x4000];
-        let values: Vec<u8> = vec![0xFF, 0xFF, 0x00, 0xFF, 0xFF, 0x03];
-        let rom_bank_values: Vec<u8> = vec![0x01, 0x1F, 0x01, 0x1F, 0x61];
-        let mem_loc_values: Vec<u8> = vec![0xFF, 0x00, 0x00, 0x00, 0x00];
-
-        for i in 0..memory_locations.len()
-        {
-            mmu.rom_bank = 0x01;
-            mmu.mem_map[memory_locations[i]] = 0;
-            mmu.set_to_memory(memory_locations[i], values[i], true);
-            assert_eq!(mmu.rom_bank, rom_bank_values[i]);
-            assert_eq!(mmu.mem_map[memory_locations[i]], mem_loc_values[i]);
-        }
-    }
-    
     #[test]
     fn passing_bad_filename_to_read_gb_file_return_empty_vec()
     {
@@ -397,38 +328,155 @@ mod mmu_tests
         assert_eq!(return_vector, vec![0;0x10000]);
     }
 
+    //Four bank MBC1 image with the same marker bytes as cpu_instrs.gb
+    fn write_mbc1_rom(name: &str) -> String
+    {
+        let rom_file = env::temp_dir().join(name).to_string_lossy().into_owned();
+        let mut rom = vec![0; 0x10000];
+        rom[0x0000] = 0x3C;
+        rom[0x0147] = 0x01;
+        rom[0x0148] = 0x01;
+        for bank in 1..4
+        {
+            rom[bank * 0x4000] = 0xC3;
+        }
+        rom[0x4300] = 0x3E;
+        rom[0x8300] = 0xE0;
+        rom[0xC900] = 0x72;
+        fs::write(&rom_file, &rom).unwrap();
+        return rom_file;
+    }
+
     #[test]
     fn initialize_catridge_test()
     {
+        let rom_file = write_mbc1_rom("rustboy_initialize_cartridge_test.gb");
         let mut mmu = MMU::new();
-        mmu.initialize_cartridge("roms/cpu_instrs.gb");
+        mmu.initialize_cartridge(&rom_file, None).unwrap();
         assert_eq!(mmu.rom_size, 4);
         assert_eq!(mmu.ram_size, 0);
         assert_eq!(mmu.cartridge_type, 1);
-        assert_eq!(mmu.memory_banks[0][0x0000], 0x3C);
-        assert_eq!(mmu.memory_banks[1][0x0000], 0xC3);
-        assert_eq!(mmu.memory_banks[2][0x0000], 0xC3);
-        assert_eq!(mmu.memory_banks[3][0x0000], 0xC3);
+        assert_eq!(mmu.mapper.memory().rom_banks[0][0x0000], 0x3C);
+        assert_eq!(mmu.mapper.memory().rom_banks[1][0x0000], 0xC3);
+        assert_eq!(mmu.mapper.memory().rom_banks[2][0x0000], 0xC3);
+        assert_eq!(mmu.mapper.memory().rom_banks[3][0x0000], 0xC3);
+        fs::remove_file(&rom_file).unwrap();
     }
 
     #[test]
     fn rom_bank_switch_test() 
     {
+        let rom_file = write_mbc1_rom("rustboy_rom_bank_switch_test.gb");
         let mut mmu = MMU::new();
-        mmu.initialize_cartridge("roms/cpu_instrs.gb");
+        mmu.initialize_cartridge(&rom_file, None).unwrap();
         assert_eq!(mmu.rom_size, 4);
         assert_eq!(mmu.ram_size, 0);
         assert_eq!(mmu.cartridge_type, 1);
         assert_eq!(mmu.get_from_memory(0x0000, false), 0x3C);
         assert_eq!(mmu.get_from_memory(0x4300, false), 0x3E);
-        mmu.mbc1_parse(0x2000, 0x00);
+        mmu.set_to_memory(0x2000, 0x00, true);
         assert_eq!(mmu.get_from_memory(0x4300, false), 0x3E);
-        mmu.mbc1_parse(0x2000, 0x02);
+        mmu.set_to_memory(0x2000, 0x02, true);
         assert_eq!(mmu.get_from_memory(0x4300, false), 0xE0);
-        mmu.mbc1_parse(0x2000, 0x03);
+        mmu.set_to_memory(0x2000, 0x03, true);
         assert_eq!(mmu.get_from_memory(0x4900, false), 0x72);
-        mmu.mbc1_parse(0x2000, 0x00);
+        mmu.set_to_memory(0x2000, 0x00, true);
         assert_eq!(mmu.get_from_memory(0x4300, false), 0x3E);
+        fs::remove_file(&rom_file).unwrap();
+    }
+
+    #[test]
+    fn rom_only_write_test()
+    {
+        let mut mmu = MMU::new();
+        mmu.mem_map[0x2000] = 0x3C;
+        mmu.set_to_memory(0x2000, 0x01, true);
+        assert_eq!(mmu.get_from_memory(0x2000, false), 0x3C);
+        mmu.set_to_memory(0xA000, 0x12, true);
+        assert_eq!(mmu.get_from_memory(0xA000, false), 0xFF);
+        assert_eq!(mmu.mem_map[0xA000], 0x00);
+
+        mmu.mapper = create_mapper(0x08, CartridgeMemory::new(2, 0x2000)).unwrap();
+        mmu.set_to_memory(0xA000, 0x12, true);
+        assert_eq!(mmu.get_from_memory(0xA000, false), 0x12);
+    }
+
+    #[test]
+    fn unsupported_cartridge_test()
+    {
+        let rom_file = env::temp_dir().join("rustboy_unsupported_cartridge_test.gb").to_string_lossy().into_owned();
+        let mut rom = vec![0; 0x8000];
+        rom[0x0147] = 0xFC;
+        fs::write(&rom_file, &rom).unwrap();
+        let mut mmu = MMU::new();
+        assert_eq!(mmu.initialize_cartridge(&rom_file, None), Err(LoadError::UnsupportedMapper(0xFC)));
+        fs::remove_file(&rom_file).unwrap();
+    }
+
+    #[test]
+    fn bad_rom_file_test()
+    {
+        let mut mmu = MMU::new();
+        assert_eq!(mmu.initialize_cartridge("roms/does_not_exist.gb", None), Err(LoadError::NotFound(String::from("roms/does_not_exist.gb"))));
+
+        let rom_file = env::temp_dir().join("rustboy_bad_rom_file_test.gb").to_string_lossy().into_owned();
+        fs::write(&rom_file, &vec![0; 0x100]).unwrap();
+        assert_eq!(mmu.initialize_cartridge(&rom_file, None), Err(LoadError::Truncated(0x100)));
+
+        let mut rom = vec![0; 0x8000];
+        rom[0x0148] = 0x01;
+        fs::write(&rom_file, &rom).unwrap();
+        assert_eq!(mmu.initialize_cartridge(&rom_file, None), Err(LoadError::SizeMismatch { expected: 0x10000, actual: 0x8000 }));
+        assert!(mmu.header.is_none());
+
+        //An overdumped ROM loads, the extra data is ignored
+        rom.resize(0x18000, 0xFF);
+        fs::write(&rom_file, &rom).unwrap();
+        assert_eq!(mmu.initialize_cartridge(&rom_file, None), Ok(()));
+        assert_eq!(mmu.rom_size, 4);
+        assert_eq!(mmu.patch_file, None);
+        fs::remove_file(&rom_file).unwrap();
+    }
+
+    #[test]
+    fn cartridge_writes_test()
+    {
+        let mut mmu = MMU::new();
+        mmu.mapper = create_mapper(0x01, CartridgeMemory::new(4, 0)).unwrap();
+        mmu.mem_map[0x2000] = 0x12;
+        mmu.set_to_memory(0x2000, 0x34, true);
+        assert_eq!(mmu.get_from_memory(0x2000, true), 0x12);
+        mmu.set_to_memory(0x0000, 0x0A, true);
+        mmu.set_to_memory(0xA000, 0x56, true);
+        assert_eq!(mmu.get_from_memory(0xA000, true), 0xFF);
+        assert_eq!(mmu.mem_map[0xA000], 0);
+    }
+
+    #[test]
+    fn stat_write_mask_test()
+    {
+        let mut mmu = MMU::new();
+        mmu.mem_map[0xFF41] = 0x06;
+        mmu.set_to_memory(0xFF41, 0xFF, true);
+        assert_eq!(mmu.mem_map[0xFF41], 0x7E);
+        mmu.set_to_memory(0xFF41, 0x00, true);
+        assert_eq!(mmu.get_from_memory(0xFF41, true), 0x86);
+    }
+
+    #[test]
+    fn boot_rom_unmap_test()
+    {
+        let mut mmu = MMU::new();
+        mmu.mem_map[0x0000] = 0xC3;
+        mmu.mem_map[0x0100] = 0x00;
+        mmu.boot_rom = Some(vec![0x31; 0x100]);
+        assert_eq!(mmu.get_from_memory(0x0000, false), 0x31);
+        assert_eq!(mmu.get_from_memory(0x00FF, false), 0x31);
+        assert_eq!(mmu.get_from_memory(0x0100, false), 0x00);
+
+        mmu.set_to_memory(0xFF50, 0x01, true);
+        assert!(mmu.boot_rom.is_none());
+        assert_eq!(mmu.get_from_memory(0x0000, false), 0xC3);
     }
 
     #[test]
@@ -458,4 +506,42 @@ mod mmu_tests
             assert_eq!(mmu.mem_map[0xFE00+i], i as u8);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn save_file_name_test()
+    {
+        assert_eq!(save_file_name("roms/zelda.gb"), "roms/zelda.sav");
+        assert_eq!(save_file_name("pokemon"), "pokemon.sav");
+        assert_eq!(save_file_name("roms/zelda.gb.gz"), "roms/zelda.sav");
+        assert_eq!(save_file_name("roms/zelda.zip"), "roms/zelda.sav");
+    }
+
+    #[test]
+    fn battery_ram_round_trip_test()
+    {
+        let save_file = env::temp_dir().join("rustboy_battery_ram_test.sav").to_string_lossy().into_owned();
+        let mut mmu = MMU::new();
+        mmu.mapper = create_mapper(0x03, CartridgeMemory::new(4, 0x8000)).unwrap();
+        mmu.save_file = Some(save_file.clone());
+        mmu.set_to_memory(0x0000, 0x0A, true);
+        mmu.set_to_memory(0x6000, 0x01, true);
+        mmu.set_to_memory(0x4000, 0x02, true);
+        mmu.set_to_memory(0xA123, 0x45, true);
+        assert!(mmu.ram_dirty);
+
+        for _ in 0..(SAVE_DELAY_CYCLES / 16) - 1
+        {
+            mmu.battery_tick(16);
+        }
+        assert!(mmu.ram_dirty);
+        mmu.battery_tick(16);
+        assert!(!mmu.ram_dirty);
+        assert_eq!(fs::metadata(&save_file).unwrap().len(), 0x8000);
+
+        let mut loaded = MMU::new();
+        loaded.mapper = create_mapper(0x03, CartridgeMemory::new(4, 0x8000)).unwrap();
+        loaded.load_battery_ram(&save_file).unwrap();
+        assert_eq!(loaded.mapper.memory().ram[0x4123], 0x45);
+        fs::remove_file(&save_file).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use gpu::gpu_registers::ShadeProfile;
+
+//Hardware revision being emulated, games tell them apart by the registers the boot ROM leaves behind
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Model
+{
+    DMG,
+    MGB,
+    SGB,
+    CGB,
+}
+
+impl Model
+{
+    //"CLASSIC" is the name get_system_data has always used for the DMG
+    pub fn parse(name: &str) -> Option<Model>
+    {
+        match name.to_uppercase().as_ref()
+        {
+            "DMG" | "CLASSIC" => return Some(Model::DMG),
+            "MGB" | "POCKET" => return Some(Model::MGB),
+            "SGB" => return Some(Model::SGB),
+            "CGB" | "COLOR" => return Some(Model::CGB),
+            _ => return None,
+        }
+    }
+
+    //AF, BC, DE, HL and SP as the boot ROM hands them to the cartridge at 0x0100.
+    //The CGB boot ROM leaves different values behind for games without CGB support.
+    pub fn post_boot_registers(&self, cgb_game: bool) -> [u16; 5]
+    {
+        match *self
+        {
+            Model::DMG => return [0x01B0, 0x0013, 0x00D8, 0x014D, 0xFFFE],
+            Model::MGB => return [0xFFB0, 0x0013, 0x00D8, 0x014D, 0xFFFE],
+            Model::SGB => return [0x0100, 0x0014, 0x0000, 0xC060, 0xFFFE],
+            Model::CGB if cgb_game => return [0x1180, 0x0000, 0xFF56, 0x000D, 0xFFFE],
+            Model::CGB => return [0x1180, 0x0000, 0x0008, 0x007C, 0xFFFE],
+        }
+    }
+
+    //IO registers that differ from the DMG values init_emulator_state writes
+    pub fn post_boot_io(&self) -> Vec<(usize, u8)>
+    {
+        match *self
+        {
+            Model::DMG | Model::MGB => return vec![],
+            //The SGB boot ROM leaves the APU status without channel 1 running
+            Model::SGB => return vec![(0xFF26, 0xF0)],
+            //Speed switch, VRAM bank and WRAM bank registers
+            Model::CGB => return vec![(0xFF4D, 0x7E), (0xFF4F, 0xFE), (0xFF70, 0xF8)],
+        }
+    }
+
+    pub fn shade_profile(&self) -> ShadeProfile
+    {
+        match *self
+        {
+            Model::DMG => return ShadeProfile::new(0),
+            Model::MGB => return ShadeProfile::new(3),
+            Model::SGB => return ShadeProfile::new(4),
+            Model::CGB => return ShadeProfile::new(5),
+        }
+    }
+}
+
+#[cfg(test)]
+mod model_tests
+{
+    use model::*;
+    use image::Rgba;
+
+    #[test]
+    fn parse_model_test()
+    {
+        assert_eq!(Model::parse("dmg"), Some(Model::DMG));
+        assert_eq!(Model::parse("CLASSIC"), Some(Model::DMG));
+        assert_eq!(Model::parse("Mgb"), Some(Model::MGB));
+        assert_eq!(Model::parse("sgb"), Some(Model::SGB));
+        assert_eq!(Model::parse("cgb"), Some(Model::CGB));
+        assert_eq!(Model::parse("gba"), None);
+    }
+
+    #[test]
+    fn post_boot_state_test()
+    {
+        assert_eq!(Model::DMG.post_boot_registers(false)[0] >> 8, 0x01);
+        assert_eq!(Model::MGB.post_boot_registers(false)[0] >> 8, 0xFF);
+        assert_eq!(Model::SGB.post_boot_registers(false)[0] >> 8, 0x01);
+        assert_eq!(Model::CGB.post_boot_registers(false)[0] >> 8, 0x11);
+        assert_eq!(Model::CGB.post_boot_registers(true)[2], 0xFF56);
+        assert_eq!(Model::CGB.post_boot_registers(false)[2], 0x0008);
+        assert_eq!(Model::SGB.post_boot_io(), vec![(0xFF26, 0xF0)]);
+        assert_eq!(Model::CGB.shade_profile().shade_0, Rgba([0xFF, 0xFF, 0xFF, 0xFF]));
+        let pocket = Model::MGB.shade_profile();
+        for shade in [pocket.shade_0, pocket.shade_1, pocket.shade_2, pocket.shade_3].iter()
+        {
+            assert!(shade[0] == shade[1] && shade[1] == shade[2]);
+        }
+    }
+}
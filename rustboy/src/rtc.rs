@@ -0,0 +1,253 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static CYCLES_PER_SECOND: u32 = 4194304;
+
+//Size of the RTC footer other emulators append to MBC3 save files
+pub static RTC_FOOTER_SIZE: usize = 48;
+
+//MBC3 clock registers, mapped to 0xA000 when RAM bank 0x08-0x0C is selected
+pub struct RealTimeClock
+{
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours: u8,
+    pub days: u16,
+    pub halt: bool,
+    pub day_carry: bool,
+    pub latched: [u8; 5],
+    pub latch_value: u8,
+    pub cycles: u32,
+}
+
+impl RealTimeClock
+{
+    pub fn new() -> RealTimeClock
+    {
+        return RealTimeClock
+        {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halt: false,
+            day_carry: false,
+            latched: [0; 5],
+            latch_value: 0xFF,
+            cycles: 0,
+        }
+    }
+
+    fn registers(&self) -> [u8; 5]
+    {
+        let mut day_high = ((self.days >> 8) & 0x01) as u8;
+        if self.halt
+        {
+            day_high |= 0x40;
+        }
+        if self.day_carry
+        {
+            day_high |= 0x80;
+        }
+        return [self.seconds, self.minutes, self.hours, (self.days & 0xFF) as u8, day_high];
+    }
+
+    pub fn read_register(&self, register: u8) -> u8
+    {
+        match register
+        {
+            0x08...0x0C => return self.latched[(register - 0x08) as usize],
+            _ => return 0xFF,
+        }
+    }
+
+    pub fn write_register(&mut self, register: u8, value: u8)
+    {
+        match register
+        {
+            0x08 =>
+            {
+                self.seconds = value & 0x3F;
+                self.cycles = 0;
+            },
+            0x09 => self.minutes = value & 0x3F,
+            0x0A => self.hours = value & 0x1F,
+            0x0B => self.days = (self.days & 0x100) | value as u16,
+            0x0C =>
+            {
+                self.days = (self.days & 0xFF) | ((value as u16 & 0x01) << 8);
+                self.halt = (value & 0x40) == 0x40;
+                self.day_carry = (value & 0x80) == 0x80;
+            },
+            _ => (),
+        }
+    }
+
+    //Writing 0x00 then 0x01 copies the running clock into the readable registers
+    pub fn write_latch(&mut self, value: u8)
+    {
+        if self.latch_value == 0x00 && value == 0x01
+        {
+            self.latched = self.registers();
+        }
+        self.latch_value = value;
+    }
+
+    pub fn add_seconds(&mut self, seconds: u64)
+    {
+        if self.halt
+        {
+            return;
+        }
+        let total_seconds = self.seconds as u64 + seconds;
+        self.seconds = (total_seconds % 60) as u8;
+        let total_minutes = self.minutes as u64 + total_seconds / 60;
+        self.minutes = (total_minutes % 60) as u8;
+        let total_hours = self.hours as u64 + total_minutes / 60;
+        self.hours = (total_hours % 24) as u8;
+        let total_days = self.days as u64 + total_hours / 24;
+        if total_days > 0x1FF
+        {
+            self.day_carry = true;
+        }
+        self.days = (total_days % 0x200) as u16;
+    }
+
+    pub fn tick(&mut self, cycles: u8)
+    {
+        if self.halt
+        {
+            return;
+        }
+        self.cycles += cycles as u32;
+        if self.cycles >= CYCLES_PER_SECOND
+        {
+            self.cycles -= CYCLES_PER_SECOND;
+            self.add_seconds(1);
+        }
+    }
+
+    //Current and latched registers as 32-bit values, then a 64-bit unix timestamp
+    pub fn to_footer(&self, timestamp: u64) -> Vec<u8>
+    {
+        let mut footer = Vec::with_capacity(RTC_FOOTER_SIZE);
+        for value in self.registers().iter().chain(self.latched.iter())
+        {
+            footer.extend_from_slice(&[*value, 0, 0, 0]);
+        }
+        for i in 0..8
+        {
+            footer.push((timestamp >> (i * 8)) as u8);
+        }
+        return footer;
+    }
+
+    //Restores the clock and runs it forward by the time that passed since the save
+    pub fn load_footer(&mut self, footer: &[u8], now: u64)
+    {
+        for i in 0..5
+        {
+            self.write_register(0x08 + i as u8, footer[i * 4]);
+            self.latched[i] = footer[(i + 5) * 4];
+        }
+        let mut timestamp: u64 = 0;
+        for i in 0..8
+        {
+            timestamp |= (footer[40 + i] as u64) << (i * 8);
+        }
+        if now > timestamp
+        {
+            self.add_seconds(now - timestamp);
+        }
+    }
+}
+
+pub fn unix_time() -> u64
+{
+    match SystemTime::now().duration_since(UNIX_EPOCH)
+    {
+        Ok(duration) => return duration.as_secs(),
+        Err(_) => return 0,
+    }
+}
+
+#[cfg(test)]
+mod rtc_tests
+{
+    use rtc::*;
+
+    #[test]
+    fn latch_test()
+    {
+        let mut rtc = RealTimeClock::new();
+        rtc.write_register(0x08, 30);
+        rtc.write_register(0x0C, 0x01);
+        assert_eq!(rtc.read_register(0x08), 0);
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read_register(0x08), 0);
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read_register(0x08), 30);
+        assert_eq!(rtc.read_register(0x0C), 0x01);
+        rtc.add_seconds(5);
+        assert_eq!(rtc.read_register(0x08), 30);
+        assert_eq!(rtc.read_register(0x0D), 0xFF);
+    }
+
+    #[test]
+    fn rollover_test()
+    {
+        let mut rtc = RealTimeClock::new();
+        rtc.write_register(0x08, 59);
+        rtc.write_register(0x09, 59);
+        rtc.write_register(0x0A, 23);
+        rtc.write_register(0x0B, 0xFF);
+        rtc.add_seconds(1);
+        assert_eq!((rtc.seconds, rtc.minutes, rtc.hours, rtc.days), (0, 0, 0, 0x100));
+        assert!(!rtc.day_carry);
+
+        rtc.write_register(0x0B, 0xFF);
+        rtc.write_register(0x0C, 0x01);
+        rtc.add_seconds(24 * 60 * 60);
+        assert_eq!(rtc.days, 0);
+        assert!(rtc.day_carry);
+    }
+
+    #[test]
+    fn halt_and_tick_test()
+    {
+        let mut rtc = RealTimeClock::new();
+        for _ in 0..(4194304 / 16)
+        {
+            rtc.tick(16);
+        }
+        assert_eq!(rtc.seconds, 1);
+
+        rtc.write_register(0x0C, 0x40);
+        rtc.add_seconds(100);
+        for _ in 0..(4194304 / 16)
+        {
+            rtc.tick(16);
+        }
+        assert_eq!(rtc.seconds, 1);
+    }
+
+    #[test]
+    fn footer_test()
+    {
+        let mut rtc = RealTimeClock::new();
+        rtc.write_register(0x08, 10);
+        rtc.write_register(0x0A, 5);
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+        let footer = rtc.to_footer(1000);
+        assert_eq!(footer.len(), RTC_FOOTER_SIZE);
+        assert_eq!(&footer[0..4], &[10, 0, 0, 0]);
+        assert_eq!(&footer[20..24], &[10, 0, 0, 0]);
+        assert_eq!(&footer[40..48], &[0xE8, 0x03, 0, 0, 0, 0, 0, 0]);
+
+        let mut loaded = RealTimeClock::new();
+        loaded.load_footer(&footer, 1000 + 3600 + 5);
+        assert_eq!((loaded.seconds, loaded.minutes, loaded.hours), (15, 0, 6));
+        assert_eq!(loaded.read_register(0x0A), 5);
+    }
+}
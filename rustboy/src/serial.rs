@@ -0,0 +1,308 @@
+use link::{LinkCable, LinkMessage, SYNC_CYCLES};
+
+pub struct Serial
+{
+    pub transfer_data: u8,
+    pub transfer_control: u8,
+    pub transferring: bool,
+    pub bit_cycles: u16,
+    pub bits_remaining: u8,
+    //Every byte sent is kept in output while this is set, for test harnesses
+    pub capture_output: bool,
+    pub output: Vec<u8>,
+    pub link: Option<LinkCable>,
+    pub link_reply: Option<u8>,
+    //Byte from the other side and the cycles left until its clock finishes shifting it in
+    pub link_transfer: Option<(u8, u16)>,
+}
+
+//Internal clock runs at 8192Hz, one bit every 512 cycles
+static BIT_CYCLES: u16 = 512;
+
+impl Serial
+{
+    pub fn new() -> Serial
+    {
+        return Serial
+        {
+            transfer_data: 0,
+            transfer_control: 0,
+            transferring: false,
+            bit_cycles: 0,
+            bits_remaining: 0,
+            capture_output: false,
+            output: Vec::new(),
+            link: None,
+            link_reply: None,
+            link_transfer: None,
+        }
+    }
+
+    pub fn update_registers(&mut self, mem_map: &Vec<u8>)
+    {
+        self.transfer_data = mem_map[0xFF01];
+        self.transfer_control = mem_map[0xFF02];
+    }
+
+    pub fn write_registers(&self, mem_map: &mut Vec<u8>)
+    {
+        mem_map[0xFF01] = self.transfer_data;
+        mem_map[0xFF02] = self.transfer_control;
+    }
+
+    pub fn internal_clock(&self) -> bool
+    {
+        return (self.transfer_control & 0x01) == 0x01;
+    }
+
+    fn capture(&mut self, value: u8)
+    {
+        if self.capture_output
+        {
+            self.output.push(value);
+        }
+    }
+
+    fn start_transfer(&mut self)
+    {
+        let transfer_data = self.transfer_data;
+        self.capture(transfer_data);
+        self.transferring = true;
+        self.bit_cycles = 0;
+        self.bits_remaining = 8;
+        if let Some(ref mut link) = self.link
+        {
+            link.send_transfer(self.transfer_data);
+            self.link_reply = None;
+        }
+    }
+
+    //Swaps messages with the other side once per slice
+    fn update_link(&mut self, cycles: u8)
+    {
+        let messages = match self.link
+        {
+            Some(ref mut link) => link.add_cycles(cycles),
+            None => return,
+        };
+        let messages = match messages
+        {
+            Ok(messages) => messages,
+            Err(error) =>
+            {
+                println!("Link cable disconnected: {}", error);
+                self.link = None;
+                return;
+            },
+        };
+
+        for message in messages
+        {
+            match message
+            {
+                LinkMessage::Transfer(value, start_cycles) =>
+                {
+                    let mut reply = 0xFF;
+                    if (self.transfer_control & 0x81) == 0x80
+                    {
+                        //The other side started start_cycles into the slice that just ended
+                        let elapsed = SYNC_CYCLES as u16 - start_cycles.min(SYNC_CYCLES as u16);
+                        reply = self.transfer_data;
+                        self.link_transfer = Some((value, (BIT_CYCLES * 8).saturating_sub(elapsed)));
+                    }
+                    if let Some(ref mut link) = self.link
+                    {
+                        link.send_reply(reply);
+                    }
+                },
+                LinkMessage::Reply(value) => self.link_reply = Some(value),
+            }
+        }
+    }
+
+    //Counts down a transfer clocked by the other side, true once its eight bits are in
+    fn link_transfer_tick(&mut self, cycles: u8) -> bool
+    {
+        let (value, remaining) = match self.link_transfer
+        {
+            Some(link_transfer) => link_transfer,
+            None => return false,
+        };
+        if remaining > cycles as u16
+        {
+            self.link_transfer = Some((value, remaining - cycles as u16));
+            return false;
+        }
+        self.link_transfer = None;
+        let transfer_data = self.transfer_data;
+        self.capture(transfer_data);
+        self.transfer_data = value;
+        self.transfer_control &= 0x7F;
+        return true;
+    }
+
+    //Returns true when a transfer completes and the serial interrupt should be requested
+    pub fn tick(&mut self, cycles: u8) -> bool
+    {
+        let link_interrupt_flag = self.link_transfer_tick(cycles);
+        //Started before the link catches up so the transfer is timed from the start of this tick
+        if !self.transferring && (self.transfer_control & 0x80) == 0x80 && self.internal_clock()
+        {
+            self.start_transfer();
+        }
+        self.update_link(cycles);
+        if !self.transferring
+        {
+            return link_interrupt_flag;
+        }
+
+        self.bit_cycles += cycles as u16;
+        if self.link.is_some()
+        {
+            //The other side's byte arrives whole, so swap it in once all eight bits are clocked
+            if self.bit_cycles >= BIT_CYCLES * 8
+            {
+                if let Some(reply) = self.link_reply.take()
+                {
+                    self.transfer_data = reply;
+                    self.bits_remaining = 0;
+                }
+            }
+        }
+        else
+        {
+            while self.bit_cycles >= BIT_CYCLES && self.bits_remaining > 0
+            {
+                self.bit_cycles -= BIT_CYCLES;
+                //Nothing is connected, so the line reads high
+                self.transfer_data = (self.transfer_data << 1) | 0x01;
+                self.bits_remaining -= 1;
+            }
+        }
+
+        if self.bits_remaining == 0
+        {
+            self.transferring = false;
+            self.transfer_control &= 0x7F;
+            return true;
+        }
+        return link_interrupt_flag;
+    }
+}
+
+#[cfg(test)]
+mod serial_tests
+{
+    use serial::Serial;
+    use system::*;
+    use link::LinkCable;
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    #[test]
+    fn update_register_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        system_data.mmu.mem_map[0xFF01] = 0x12;
+        system_data.mmu.mem_map[0xFF02] = 0x81;
+        system_data.serial.update_registers(&system_data.mmu.mem_map);
+        assert_eq!(system_data.serial.transfer_data, 0x12);
+        assert_eq!(system_data.serial.transfer_control, 0x81);
+    }
+
+    #[test]
+    fn internal_clock_transfer_test()
+    {
+        let mut serial = Serial::new();
+        serial.capture_output = true;
+        serial.transfer_data = 0x41;
+        serial.transfer_control = 0x81;
+        for i in 0..(4096 / 8) - 1
+        {
+            assert!(!serial.tick(8));
+            assert_eq!(serial.transfer_control, 0x81);
+            assert_eq!(serial.bits_remaining, 8 - ((i + 1) * 8 / 512) as u8);
+        }
+        assert!(serial.tick(8));
+        assert_eq!(serial.transfer_data, 0xFF);
+        assert_eq!(serial.transfer_control, 0x01);
+        assert_eq!(serial.output, vec![0x41]);
+        assert!(!serial.tick(8));
+    }
+
+    #[test]
+    fn external_clock_never_completes_test()
+    {
+        let mut serial = Serial::new();
+        serial.transfer_data = 0x41;
+        serial.transfer_control = 0x80;
+        for _ in 0..1000
+        {
+            assert!(!serial.tick(24));
+        }
+        assert_eq!(serial.transfer_data, 0x41);
+        assert_eq!(serial.transfer_control, 0x80);
+        assert!(serial.link_transfer.is_none());
+    }
+
+    #[test]
+    fn serial_interrupt_test()
+    {
+        let mut system_data : SystemData = get_system_data("CLASSIC");
+        system_data.serial.capture_output = true;
+        system_data.mmu.set_to_memory(0xFF01, 0x50, true);
+        system_data.mmu.set_to_memory(0xFF02, 0x81, true);
+        system_data.cycles = 16;
+        for _ in 0..256
+        {
+            system_data.serial_tick();
+        }
+        assert_eq!(system_data.mmu.get_from_memory(0xFF01, false), 0xFF);
+        assert_eq!(system_data.mmu.get_from_memory(0xFF02, false), 0x7F);
+        assert_eq!(system_data.mmu.get_from_memory(0xFF0F, false) & 0x08, 0x08);
+        assert_eq!(system_data.serial.output, vec![0x50]);
+    }
+
+    #[test]
+    fn linked_transfer_test()
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let slave_thread = thread::spawn(move ||
+        {
+            let mut serial = Serial::new();
+            serial.link = Some(LinkCable::from_stream(TcpStream::connect(address).unwrap()).unwrap());
+            serial.transfer_data = 0x34;
+            serial.transfer_control = 0x80;
+            let mut completed_at = Vec::new();
+            for i in 0..1024
+            {
+                if serial.tick(8)
+                {
+                    completed_at.push(i);
+                }
+            }
+            (serial.transfer_data, serial.transfer_control, completed_at)
+        });
+
+        let mut serial = Serial::new();
+        serial.link = Some(LinkCable::accept(&listener).unwrap());
+        serial.transfer_data = 0x12;
+        serial.transfer_control = 0x81;
+        let mut completed_at = Vec::new();
+        for i in 0..1024
+        {
+            if serial.tick(8)
+            {
+                completed_at.push(i);
+            }
+        }
+        assert_eq!(completed_at, vec![511]);
+        assert_eq!(serial.transfer_data, 0x34);
+        assert_eq!(serial.transfer_control, 0x01);
+        assert!(serial.link.is_some());
+
+        //The slave finishes on the same cycle as the master that clocks it
+        assert_eq!(slave_thread.join().unwrap(), (0x12, 0x00, vec![511]));
+    }
+}
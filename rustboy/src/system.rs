@@ -1,11 +1,17 @@
 use mmu::MMU;
 use timer::Timer;
+use serial::Serial;
+use apu::apu::APU;
+use model::Model;
 
 pub struct SystemData
 {
     pub mmu: MMU,
     pub timer: Timer,
+    pub serial: Serial,
+    pub apu: APU,
     pub input: PlayerInput,
+    pub model: Model,
     pub width: u16,
     pub tile_width: u16,
     pub height: u16,
@@ -15,6 +21,7 @@ pub struct SystemData
     pub vertical_sync: f64,
     pub cycles: u8,
     pub debug_flag1: bool,
+    pub breakpoint_flag: bool,
 }
 
 impl SystemData{
@@ -44,6 +51,38 @@ impl SystemData{
             self.mmu.set_to_memory(0xFF0F, value, false);
         }
     }   
+
+    pub fn serial_tick(&mut self)
+    {
+        self.serial.update_registers(&self.mmu.mem_map);
+        let interrupt_flag = self.serial.tick(self.cycles);
+        self.serial.write_registers(&mut self.mmu.mem_map);
+        if interrupt_flag
+        {
+            let mut value = self.mmu.get_from_memory(0xFF0F, false);
+            value |= 0x08;
+            self.mmu.set_to_memory(0xFF0F, value, false);
+        }
+    }
+
+    pub fn sound_tick(&mut self)
+    {
+        for (location, value) in self.mmu.sound_writes.drain(..)
+        {
+            self.apu.write_register(location, value);
+        }
+        let div_bit = (self.timer.cycle_register & 0x1000) != 0;
+        self.apu.tick(self.cycles, div_bit);
+        //Registers read back as zero while the APU is powered off
+        if !self.apu.power
+        {
+            for location in 0xFF10..0xFF26
+            {
+                self.mmu.mem_map[location] = 0;
+            }
+        }
+        self.mmu.mem_map[0xFF26] = self.apu.status();
+    }
 }
 
 pub struct Registers 
@@ -188,6 +227,7 @@ impl Registers{
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct PlayerInput
 {
     pub left: bool,
@@ -308,31 +348,43 @@ impl PlayerInput
     } 
 }
 
+pub fn system_data_for_model(model: Model) -> SystemData
+{
+    return SystemData
+    {
+        mmu: MMU::new(),
+        timer: Timer::new(),
+        serial: Serial::new(),
+        apu: APU::new(),
+        input: PlayerInput::new(),
+        model: model,
+        width: 160,
+        tile_width: 20,
+        height: 144,
+        tile_height: 18,
+        clock_speed: 4194304,
+        horizontal_sync: 9198000,
+        vertical_sync: 59.73,
+        cycles: 0,
+        debug_flag1: false,
+        breakpoint_flag: false,
+    }
+}
+
 pub fn get_system_data(emulator_type: &str) -> SystemData
 {
-    match emulator_type.as_ref()
+    match Model::parse(emulator_type)
     {
-        "CLASSIC" => return SystemData
-        {
-            mmu: MMU::new(),
-            timer: Timer::new(),
-            input: PlayerInput::new(),
-            width: 160,
-            tile_width: 20,
-            height: 144,
-            tile_height: 18,
-            clock_speed: 4194304,
-            horizontal_sync: 9198000,
-            vertical_sync: 59.73,
-            cycles: 0,
-            debug_flag1: false,
-        },
-        _ => {println!("NOT VALID EMULATOR TYPE");
+        Some(model) => return system_data_for_model(model),
+        None => {println!("NOT VALID EMULATOR TYPE");
         return SystemData
         {
             mmu: MMU::new(),
             timer: Timer::new(),
+            serial: Serial::new(),
+            apu: APU::new(),
             input: PlayerInput::new(),
+            model: Model::DMG,
             width: 0,
             tile_width: 0,
             height: 0,
@@ -342,6 +394,7 @@ pub fn get_system_data(emulator_type: &str) -> SystemData
             vertical_sync: 0.0,
             cycles: 0,
             debug_flag1: false,
+            breakpoint_flag: false,
         }},
 
     }
@@ -354,8 +407,9 @@ pub fn get_system_data(emulator_type: &str) -> SystemData
 mod main_tests
 {
 
-    use get_system_data;
-    use SystemData;
+    use system::get_system_data;
+    use system::SystemData;
+    use model::Model;
 
     #[test]
     fn passing_bad_data_to_get_system_data_returns_empty_struct_data()
@@ -372,4 +426,15 @@ mod main_tests
         assert_eq!(system_data.cycles, 0);
         
     }
+
+    #[test]
+    fn get_system_data_for_model_test()
+    {
+        let system_data : SystemData = get_system_data("CLASSIC");
+        assert_eq!(system_data.model, Model::DMG);
+        assert_eq!(system_data.width, 160);
+        let system_data : SystemData = get_system_data("sgb");
+        assert_eq!(system_data.model, Model::SGB);
+        assert_eq!(system_data.height, 144);
+    }
 }
\ No newline at end of file
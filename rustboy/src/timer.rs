@@ -153,7 +153,7 @@ impl Timer
 #[cfg(test)]
 mod timer_tests
 {
-    use Timer;
+    use timer::Timer;
     use system::*;
     
     #[test]